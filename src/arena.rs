@@ -0,0 +1,1524 @@
+use crate::{
+    ArenaWriter, BackingAlloc, BufferAlloc, Error, Global, MemoryBudget, Observer, Result, Stats,
+};
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::{Cell, UnsafeCell};
+use core::ffi::CStr;
+use core::fmt::{self, Write};
+use core::hash::{Hash, Hasher};
+use core::mem::align_of;
+use core::ptr::NonNull;
+
+/// Mark is a point in time of a [`StringArena`], returned by
+/// [`StringArena::checkpoint`], which can be rolled back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Mark(usize);
+
+impl Mark {
+    /// Returns bytes written of the arena when the mark is taken.
+    #[inline]
+    pub fn offset(self) -> usize {
+        self.0
+    }
+}
+
+/// Byte filled into discarded regions with feature `poison`.
+#[cfg(feature = "poison")]
+pub const POISON: u8 = 0xa5;
+
+/// StringArenaRef is a string arena over a caller-provided buffer.
+pub type StringArenaRef<'a> = StringArena<BufferAlloc<'a>>;
+
+/// StringArena is a single-thread append-only string arena.
+///
+/// The backing buffer is allocated by [`Global`] allocator by default,
+/// custom allocator can be provided by [`with_capacity_in`](Self::with_capacity_in).
+pub struct StringArena<A: BackingAlloc = Global> {
+    ptr: NonNull<u8>,
+    cap: usize,
+    // Alignment of the buffer, which is a power of two.
+    align: usize,
+    idx: Cell<usize>,
+    // Start of the most recent add, which can be undone by pop_last().
+    last: Cell<usize>,
+    stats: Cell<Stats>,
+    // Bumped whenever strings are discarded, so stale spans are detected.
+    generation: u32,
+    // Sorted regions of typed values, which may contain uninitialized
+    // padding and are never viewed as bytes.
+    typed: UnsafeCell<Vec<(usize, usize)>>,
+    observer: Option<Arc<dyn Observer>>,
+    budget: Option<MemoryBudget>,
+    alloc: A,
+}
+
+// SAFETY:
+//
+// The buffer is exclusively owned by the arena.
+unsafe impl<A: BackingAlloc + Send> Send for StringArena<A> {}
+
+impl StringArena {
+    /// Create a new string arena with given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::try_with_capacity(cap).unwrap()
+    }
+
+    /// Try to create a new string arena with given capacity.
+    /// Returns error if the allocation fails.
+    /// Zero capacity is allowed and does not allocate.
+    #[inline]
+    pub fn try_with_capacity(cap: usize) -> Result<Self> {
+        Self::try_with_capacity_in(cap, Global)
+    }
+}
+
+impl StringArena {
+    /// Decompose the arena into its buffer, bytes written, capacity and
+    /// alignment. The observer is dropped and the budget is released.
+    #[inline]
+    pub(crate) fn into_raw_parts(mut self) -> (NonNull<u8>, usize, usize, usize) {
+        let parts = (self.ptr, self.len(), self.cap, self.align);
+        // Give up ownership of the buffer, so drop releases everything
+        // else but the buffer.
+        self.cap = 0;
+        parts
+    }
+
+    /// Create an arena from raw parts returned by
+    /// [`into_raw_parts`](Self::into_raw_parts).
+    ///
+    /// # Safety
+    ///
+    /// The raw parts must be returned by `into_raw_parts` and not used
+    /// to create another arena.
+    #[inline]
+    pub(crate) unsafe fn from_raw_parts(
+        ptr: NonNull<u8>,
+        len: usize,
+        cap: usize,
+        align: usize,
+    ) -> Self {
+        StringArena {
+            ptr,
+            cap,
+            align,
+            idx: Cell::new(len),
+            last: Cell::new(len),
+            stats: Cell::new(Stats::default()),
+            generation: 0,
+            typed: UnsafeCell::new(Vec::new()),
+            observer: None,
+            budget: None,
+            alloc: Global,
+        }
+    }
+}
+
+impl<'a> StringArena<BufferAlloc<'a>> {
+    /// Create a new string arena over a caller-provided buffer.
+    /// The capacity is the length of the buffer, and no heap allocation
+    /// is performed.
+    #[inline]
+    pub fn from_buffer(buf: &'a mut [u8]) -> StringArenaRef<'a> {
+        let cap = buf.len();
+        // The buffer always fits its own length with alignment of u8.
+        Self::with_capacity_in(cap, BufferAlloc::new(buf))
+    }
+}
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Create a new string arena with given capacity and backing allocator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity_in(cap: usize, alloc: A) -> Self {
+        Self::try_with_capacity_in(cap, alloc).unwrap()
+    }
+
+    /// Try to create a new string arena with given capacity and backing allocator.
+    /// Returns error if the allocation fails.
+    /// Zero capacity is allowed and does not allocate.
+    #[inline]
+    pub fn try_with_capacity_in(cap: usize, alloc: A) -> Result<Self> {
+        Self::try_with_capacity_aligned_in(cap, align_of::<u8>(), alloc)
+    }
+
+    /// Try to create a new string arena with given capacity, alignment of
+    /// buffer and backing allocator.
+    /// Returns error if the alignment is not a power of two, or the
+    /// allocation fails.
+    #[inline]
+    pub(crate) fn try_with_capacity_aligned_in(cap: usize, align: usize, alloc: A) -> Result<Self> {
+        let layout = Layout::from_size_align(cap, align).map_err(|_| Error::AllocFailed(cap))?;
+        let ptr = if cap == 0 {
+            dangling(align)
+        } else {
+            alloc.allocate(layout).ok_or(Error::AllocFailed(cap))?
+        };
+        Ok(StringArena{ptr, cap, align, idx: Cell::new(0), last: Cell::new(0), stats: Cell::new(Stats::default()), generation: 0, typed: UnsafeCell::new(Vec::new()), observer: None, budget: None, alloc})
+    }
+
+    /// Set the observer notified of events of the arena.
+    /// The observer is kept by clones of the arena.
+    #[inline]
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn Observer>>) {
+        self.observer = observer;
+    }
+
+    /// Register the arena with given memory budget, charging bytes
+    /// written to it, and releasing them from the previous budget.
+    /// Fails if the bytes exceed the new budget, and nothing is changed.
+    /// Clones of the arena are not registered.
+    #[inline]
+    pub fn set_budget(&mut self, budget: Option<MemoryBudget>) -> Result<()> {
+        if let Some(budget) = &budget {
+            budget.charge(self.len())?;
+        }
+        if let Some(old) = core::mem::replace(&mut self.budget, budget) {
+            old.release(self.len());
+        }
+        Ok(())
+    }
+
+    /// Returns alignment of the buffer.
+    #[inline]
+    pub fn alignment(&self) -> usize {
+        self.align
+    }
+
+    /// Returns the backing allocator.
+    #[inline]
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Returns bytes written of current arena.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.idx.get()
+    }
+
+    /// Returns whether the arena is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns capacity of current arena.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Returns number of strings added since creation or last clear.
+    #[inline]
+    pub fn string_count(&self) -> usize {
+        self.stats.get().strings
+    }
+
+    /// Returns statistics of strings added since creation or last clear.
+    #[inline]
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// Returns the pointer of the buffer for exclusive access.
+    #[inline]
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns bytes remained of current arena.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.cap - self.len()
+    }
+
+    /// Returns whether given bytes can be added into current arena.
+    /// Alignment padding required by typed values is not taken into account.
+    #[inline]
+    pub fn can_fit(&self, len: usize) -> bool {
+        len <= self.remaining()
+    }
+
+    /// Add a string into current arena.
+    /// Returns the string ref if succeeds.
+    /// The only reason of failure is that input string exceeds remained capacity.
+    /// The additional bytes required to store it is returned if fails. 
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        let s = s.as_ref();
+        let bs = self.add_bytes(s.as_bytes())?;
+        // SAFETY:
+        //
+        // The bytes are copied from valid UTF-8.
+        unsafe { Ok(core::str::from_utf8_unchecked(bs)) }
+    }
+
+    /// Add raw bytes into current arena.
+    /// Returns the byte slice ref if succeeds.
+    /// The bytes are not required to be valid UTF-8.
+    #[inline]
+    pub fn add_bytes<T: AsRef<[u8]>>(&self, bs: T) -> Result<&[u8]> {
+        let idx = self.len();
+        let res = self.push_bytes(bs.as_ref())?;
+        self.record(idx);
+        Ok(res)
+    }
+
+    /// Add a string into current arena if it fits, otherwise copy it into
+    /// an owned string on heap.
+    /// Returns borrowed string ref if it is stored in the arena.
+    #[inline]
+    pub fn add_or_heap<T: AsRef<str>>(&self, s: T) -> Cow<'_, str> {
+        let s = s.as_ref();
+        match self.add(s) {
+            Ok(res) => Cow::Borrowed(res),
+            Err(_) => Cow::Owned(String::from(s)),
+        }
+    }
+
+    /// Copy raw bytes to the end of current arena, without recording
+    /// them as an add, for additions composed of multiple writes.
+    #[inline]
+    pub(crate) fn push_bytes(&self, bs: &[u8]) -> Result<&[u8]> {
+        let len = bs.len();
+        let remaining = self.remaining();
+        if remaining < len {
+            return Err(self.reject(len, len - remaining))
+        }
+        self.charge(len)?;
+        // SAFETY:
+        //
+        // Capacity is checked above.
+        Ok(unsafe { self.push_unchecked(bs) })
+    }
+
+    /// Copy raw bytes to the end of current arena, without any check.
+    ///
+    /// # Safety
+    ///
+    /// Caller must make sure the bytes fit remained capacity, and are
+    /// charged to the budget.
+    #[inline]
+    unsafe fn push_unchecked(&self, bs: &[u8]) -> &[u8] {
+        let idx = self.len();
+        let len = bs.len();
+        // The region after idx is never referenced, and the input does not
+        // overlap with it.
+        let dst = self.ptr.as_ptr().add(idx);
+        core::ptr::copy_nonoverlapping(bs.as_ptr(), dst, len);
+        self.idx.set(idx + len);
+        core::slice::from_raw_parts(dst, len)
+    }
+
+    /// Add all strings into current arena.
+    /// Returns string refs in input order if succeeds.
+    /// Capacity is checked up front, so either all strings are added or
+    /// nothing is kept in the arena.
+    #[inline]
+    pub fn add_all<'a, I: IntoIterator<Item = &'a str>>(&self, it: I) -> Result<Vec<&str>> {
+        let strs: Vec<&str> = it.into_iter().collect();
+        let mut res = vec![""; strs.len()];
+        self.add_all_to(&strs, &mut res)?;
+        Ok(res)
+    }
+
+    /// Add all strings into current arena, storing string refs into given
+    /// slice in input order.
+    /// Capacity is checked up front, so either all strings are added or
+    /// nothing is kept in the arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if lengths of input and output differ.
+    #[inline]
+    pub fn add_all_to<'a>(&'a self, strs: &[&str], out: &mut [&'a str]) -> Result<()> {
+        assert_eq!(strs.len(), out.len(), "mismatched output length");
+        let total = strs
+            .iter()
+            .try_fold(0usize, |acc, s| acc.checked_add(s.len()))
+            .unwrap_or(usize::MAX);
+        let remaining = self.remaining();
+        if remaining < total {
+            return Err(self.reject(total, total - remaining));
+        }
+        self.charge(total)?;
+        let start = self.len();
+        for (s, o) in strs.iter().zip(out.iter_mut()) {
+            let idx = self.len();
+            // SAFETY:
+            //
+            // Capacity and budget are checked above, and the bytes are
+            // valid UTF-8.
+            *o = unsafe { core::str::from_utf8_unchecked(self.push_unchecked(s.as_bytes())) };
+            self.record(idx);
+        }
+        // The whole batch is undone by pop_last().
+        self.last.set(start);
+        Ok(())
+    }
+
+    /// Copy all bytes written of another arena into current arena.
+    /// Fails if they exceed remained capacity, and nothing is kept in
+    /// the arena.
+    #[inline]
+    pub fn append_arena<B: BackingAlloc>(&mut self, other: &StringArena<B>) -> Result<()> {
+        let idx = self.len();
+        self.copy_from(other, 0, other.len())?;
+        self.record(idx);
+        Ok(())
+    }
+
+    /// Copy given written region of another arena to the end of current
+    /// arena, keeping typed values of the region typed, without recording
+    /// it as an add.
+    fn copy_from<B>(&self, other: &StringArena<B>, start: usize, end: usize) -> Result<()>
+    where
+        B: BackingAlloc,
+    {
+        let idx = self.len();
+        let dst = self.reserve_layout(Layout::from_size_align(end - start, 1).unwrap())?;
+        // SAFETY:
+        //
+        // The destination is reserved above, and the source is within
+        // written bytes of another arena. Bytes are copied untyped, so
+        // uninitialized bytes are kept as is.
+        unsafe {
+            let src = other.ptr.as_ptr().add(start);
+            core::ptr::copy_nonoverlapping(src, dst.as_ptr(), end - start);
+        }
+        for &(from, to) in other.typed_ref() {
+            if from < end && start < to {
+                self.mark_typed(idx + from.max(start) - start, idx + to.min(end) - start);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a writer which formats a string directly into current arena.
+    #[inline]
+    pub fn writer(&self) -> ArenaWriter<'_, A> {
+        ArenaWriter::new(self)
+    }
+
+    /// Returns a writer which writes bytes directly into current arena
+    /// through [`std::io::Write`].
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn byte_writer(&self) -> crate::ArenaByteWriter<'_, A> {
+        crate::ArenaByteWriter::new(self)
+    }
+
+    /// Format a string directly into current arena.
+    /// Returns the string ref if succeeds.
+    /// Nothing is kept in the arena if formatting fails.
+    /// See also [`arena_write!`](crate::arena_write).
+    #[inline]
+    pub fn add_fmt(&self, args: fmt::Arguments<'_>) -> Result<&str> {
+        if let Some(s) = args.as_str() {
+            return self.add(s);
+        }
+        let mut w = self.writer();
+        if w.write_fmt(args).is_err() {
+            // Errors of the arena are kept in writer, others come from
+            // formatting trait implementations.
+            w.fail(Error::FormatFailed);
+        }
+        w.finish()
+    }
+
+    /// Add a string with a trailing NUL into current arena.
+    /// Returns the C string ref if succeeds.
+    /// Fails if the string contains interior NUL.
+    #[inline]
+    pub fn add_cstr<T: AsRef<str>>(&self, s: T) -> Result<&CStr> {
+        let bs = s.as_ref().as_bytes();
+        if let Some(pos) = bs.iter().position(|&b| b == 0) {
+            return Err(Error::InteriorNul(pos));
+        }
+        let len = bs.len() + 1;
+        let dst = self.alloc_layout(Layout::array::<u8>(len).unwrap())?.as_ptr();
+        // SAFETY:
+        //
+        // The destination is reserved for the bytes and the trailing NUL.
+        unsafe {
+            core::ptr::copy_nonoverlapping(bs.as_ptr(), dst, bs.len());
+            dst.add(bs.len()).write(0);
+            let bs = core::slice::from_raw_parts(dst, len);
+            Ok(CStr::from_bytes_with_nul_unchecked(bs))
+        }
+    }
+
+    /// Add a value into current arena, properly aligned.
+    /// Returns the value ref if succeeds.
+    #[inline]
+    pub fn add_value<T: Copy>(&self, v: T) -> Result<&T> {
+        self.add_slice(core::slice::from_ref(&v)).map(|vs| &vs[0])
+    }
+
+    /// Add a slice of values into current arena, properly aligned.
+    /// Returns the slice ref if succeeds.
+    /// Values may contain uninitialized padding, so they are compared and
+    /// hashed by position only, see [`PartialEq`] of the arena.
+    #[inline]
+    pub fn add_slice<T: Copy>(&self, vs: &[T]) -> Result<&[T]> {
+        let dst = self.alloc_typed(Layout::for_value(vs))?.as_ptr() as *mut T;
+        // SAFETY:
+        //
+        // The destination is reserved for the slice and properly aligned.
+        unsafe {
+            core::ptr::copy_nonoverlapping(vs.as_ptr(), dst, vs.len());
+            Ok(core::slice::from_raw_parts(dst, vs.len()))
+        }
+    }
+
+    /// Reserve a region of given layout at the end of current arena,
+    /// recorded as an add.
+    /// Padding bytes before the region are zeroed, the region itself
+    /// is uninitialized.
+    #[inline]
+    pub(crate) fn alloc_layout(&self, layout: Layout) -> Result<NonNull<u8>> {
+        let idx = self.len();
+        let res = self.reserve_layout(layout)?;
+        self.record(idx);
+        Ok(res)
+    }
+
+    /// Reserve a region of given layout for typed values at the end of
+    /// current arena, recorded as an add.
+    /// The region may be left uninitialized, or contain padding of the
+    /// values, so it is never viewed as bytes.
+    #[inline]
+    pub(crate) fn alloc_typed(&self, layout: Layout) -> Result<NonNull<u8>> {
+        let idx = self.len();
+        let res = self.alloc_layout(layout)?;
+        self.mark_typed(idx, self.len());
+        Ok(res)
+    }
+
+    /// Mark given region written as typed values.
+    #[inline]
+    pub(crate) fn mark_typed(&self, start: usize, end: usize) {
+        debug_assert!(start <= end && end <= self.len());
+        if start == end {
+            return;
+        }
+        // SAFETY:
+        //
+        // The region list is guaranteed not to be accessed concurrently,
+        // and no reference to the list escapes this method.
+        let typed = unsafe { &mut *self.typed.get() };
+        match typed.last_mut() {
+            Some(last) if last.1 == start => last.1 = end,
+            _ => typed.push((start, end)),
+        }
+    }
+
+    #[inline]
+    fn typed_ref(&self) -> &[(usize, usize)] {
+        // SAFETY:
+        //
+        // The region list is only mutated inside mark_typed() and
+        // rewind(), which never run concurrently with this shared access.
+        unsafe { &*self.typed.get() }
+    }
+
+    /// Returns whether given region overlaps any typed region.
+    #[inline]
+    pub(crate) fn overlaps_typed(&self, start: usize, end: usize) -> bool {
+        let typed = self.typed_ref();
+        let i = typed.partition_point(|r| r.1 <= start);
+        typed.get(i).is_some_and(|r| r.0 < end)
+    }
+
+    /// Returns written regions which are not typed, in order.
+    #[inline]
+    fn untyped(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let end = (self.len(), self.len());
+        self.typed_ref().iter().copied().chain([end]).scan(0, |start, (from, to)| {
+            let res = (*start, from);
+            *start = to;
+            Some(res)
+        })
+    }
+
+    /// Reserve a region of given layout at the end of current arena,
+    /// without recording it as an add.
+    #[inline]
+    pub(crate) fn reserve_layout(&self, layout: Layout) -> Result<NonNull<u8>> {
+        let idx = self.len();
+        let pad = self.ptr.as_ptr().wrapping_add(idx).align_offset(layout.align());
+        let new_len = idx.saturating_add(pad).saturating_add(layout.size());
+        if self.capacity() < new_len {
+            return Err(self.reject(layout.size(), new_len - self.capacity()))
+        }
+        self.charge(new_len - idx)?;
+        let mut stats = self.stats.get();
+        stats.waste(pad);
+        self.stats.set(stats);
+        // SAFETY:
+        //
+        // The region after idx is never referenced.
+        unsafe {
+            let dst = self.ptr.as_ptr().add(idx);
+            core::ptr::write_bytes(dst, 0, pad);
+            self.idx.set(new_len);
+            Ok(NonNull::new_unchecked(dst.add(pad)))
+        }
+    }
+
+    /// Add all pieces back-to-back into current arena.
+    /// Returns the concatenated string ref if succeeds.
+    /// Nothing is kept in the arena if any piece exceeds remained capacity.
+    #[inline]
+    pub fn add_concat<I, T>(&self, parts: I) -> Result<&str>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut w = self.writer();
+        for part in parts {
+            if w.write_str(part.as_ref()).is_err() {
+                break;
+            }
+        }
+        w.finish()
+    }
+
+    /// Add all pieces interleaved with the separator into current arena.
+    /// Returns the joined string ref if succeeds.
+    /// Nothing is kept in the arena if the joined string exceeds remained
+    /// capacity.
+    #[inline]
+    pub fn add_join<I, T>(&self, parts: I, sep: &str) -> Result<&str>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut w = self.writer();
+        for (i, part) in parts.into_iter().enumerate() {
+            if (i > 0 && w.write_str(sep).is_err()) || w.write_str(part.as_ref()).is_err() {
+                break;
+            }
+        }
+        w.finish()
+    }
+
+    /// Returns the string ref of given written region.
+    ///
+    /// # Safety
+    ///
+    /// The region must be within written bytes and be valid UTF-8.
+    #[inline]
+    pub(crate) unsafe fn slice_unchecked(&self, start: usize, end: usize) -> &str {
+        core::str::from_utf8_unchecked(self.bytes_unchecked(start, end))
+    }
+
+    /// Returns the byte slice ref of given written region.
+    ///
+    /// # Safety
+    ///
+    /// The region must be within written bytes.
+    #[inline]
+    pub(crate) unsafe fn bytes_unchecked(&self, start: usize, end: usize) -> &[u8] {
+        core::slice::from_raw_parts(self.ptr.as_ptr().add(start), end - start)
+    }
+
+    /// Set bytes written of current arena.
+    ///
+    /// # Safety
+    ///
+    /// Caller must make sure no ref to bytes after new length is alive.
+    #[inline]
+    pub(crate) unsafe fn set_len(&self, len: usize) {
+        debug_assert!(len <= self.len());
+        self.rewind(len);
+    }
+
+    /// Record bytes from given start to the end as the most recent add.
+    #[inline]
+    pub(crate) fn record(&self, start: usize) {
+        debug_assert!(start <= self.len());
+        self.last.set(start);
+        let mut stats = self.stats.get();
+        stats.record(self.len() - start, self.len());
+        self.stats.set(stats);
+        if let Some(observer) = &self.observer {
+            observer.on_add(self.len() - start);
+        }
+    }
+
+    /// Record regions from start to mid, and from mid to the end of
+    /// current arena, as two adds.
+    #[inline]
+    pub(crate) fn record_pair(&self, start: usize, mid: usize) {
+        debug_assert!(start <= mid && mid <= self.len());
+        let end = self.len();
+        let mut stats = self.stats.get();
+        stats.record(mid - start, mid);
+        stats.record(end - mid, end);
+        self.stats.set(stats);
+        self.last.set(mid);
+        if let Some(observer) = &self.observer {
+            observer.on_add(mid - start);
+            observer.on_add(end - mid);
+        }
+    }
+
+    /// Charge given bytes to the budget if registered.
+    #[inline]
+    fn charge(&self, len: usize) -> Result<()> {
+        match &self.budget {
+            Some(budget) => budget.charge(len),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a failed addition of given length, and returns the error of
+    /// additional bytes required.
+    #[inline]
+    pub(crate) fn reject(&self, len: usize, additional: usize) -> Error {
+        let mut stats = self.stats.get();
+        stats.reject(len);
+        self.stats.set(stats);
+        if let Some(observer) = &self.observer {
+            observer.on_exceeds_capacity(len);
+        }
+        Error::ExceedsCapacity(additional)
+    }
+
+    /// Discard bytes after given length, which must not exceed bytes written.
+    #[inline]
+    fn rewind(&self, len: usize) {
+        #[cfg(feature = "poison")]
+        // SAFETY:
+        //
+        // The discarded region is within written bytes and never referenced.
+        unsafe {
+            core::ptr::write_bytes(self.ptr.as_ptr().add(len), POISON, self.len() - len);
+        }
+        if let Some(budget) = &self.budget {
+            budget.release(self.len() - len);
+        }
+        self.idx.set(len);
+        self.last.set(self.last.get().min(len));
+        // SAFETY:
+        //
+        // The region list is guaranteed not to be accessed concurrently,
+        // and no reference to the list escapes this method.
+        let typed = unsafe { &mut *self.typed.get() };
+        while let Some(last) = typed.last_mut() {
+            if last.0 < len {
+                last.1 = last.1.min(len);
+                break;
+            }
+            typed.pop();
+        }
+    }
+
+    /// Reserve capacity for at least additional bytes more.
+    /// The buffer may be reallocated to a larger one to avoid frequent
+    /// reallocations, like [`Vec::reserve`](alloc::vec::Vec::reserve).
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) -> Result<()> {
+        if self.can_fit(additional) {
+            return Ok(());
+        }
+        let new_cap = self
+            .len()
+            .checked_add(additional)
+            .ok_or(Error::AllocFailed(usize::MAX))?;
+        self.grow_to(new_cap.max(self.cap * 2))
+    }
+
+    /// Grow capacity of current arena to given value by reallocating the
+    /// buffer. Written bytes are kept. Nothing happens if the capacity is
+    /// already large enough.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn grow_to(&mut self, new_cap: usize) -> Result<()> {
+        if new_cap <= self.cap {
+            return Ok(());
+        }
+        self.realloc(new_cap)
+    }
+
+    /// Shrink capacity of current arena to bytes written by reallocating
+    /// the buffer, so unused memory is released.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) -> Result<()> {
+        if self.len() == self.cap {
+            return Ok(());
+        }
+        self.realloc(self.len())
+    }
+
+    /// Move written bytes into a new buffer with given capacity, which
+    /// must not be less than bytes written.
+    fn realloc(&mut self, new_cap: usize) -> Result<()> {
+        let ptr = if new_cap == 0 {
+            dangling(self.align)
+        } else {
+            let layout = Layout::from_size_align(new_cap, self.align)
+                .map_err(|_| Error::AllocFailed(new_cap))?;
+            self.alloc.allocate(layout).ok_or(Error::AllocFailed(new_cap))?
+        };
+        // SAFETY:
+        //
+        // The new buffer can hold written bytes, and the old buffer is
+        // allocated with the same layout in constructor or last reallocation.
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr.as_ptr(), self.len());
+            if self.cap != 0 {
+                let old = Layout::from_size_align_unchecked(self.cap, self.align);
+                self.alloc.deallocate(self.ptr, old);
+            }
+        }
+        self.ptr = ptr;
+        self.cap = new_cap;
+        Ok(())
+    }
+
+    /// Clear the arena so its memory can be reused.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        let len = self.len();
+        self.discard(0);
+        if len != 0 {
+            // SAFETY:
+            //
+            // The region is written before, and not referenced any more.
+            unsafe { self.alloc.purge(self.ptr, len) };
+        }
+        self.stats.set(Stats::default());
+        if let Some(observer) = &self.observer {
+            observer.on_clear(len);
+        }
+    }
+
+    /// Returns the generation of the arena, which is bumped whenever
+    /// strings are discarded by clear, truncation or pop.
+    /// Spans taken in an earlier generation are stale.
+    #[inline]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Discard bytes after given length and start a new generation.
+    #[inline]
+    pub(crate) fn discard(&mut self, len: usize) {
+        self.rewind(len);
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Returns a mark of current state, which can be rolled back to
+    /// with [`truncate_to`](Self::truncate_to).
+    #[inline]
+    pub fn checkpoint(&self) -> Mark {
+        Mark(self.len())
+    }
+
+    /// Discard all strings added since the mark is taken.
+    /// It has no effect if the arena is already shorter than the mark.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn truncate_to(&mut self, mark: Mark) {
+        if mark.0 < self.len() {
+            self.discard(mark.0);
+        }
+    }
+
+    /// Move all strings added since the mark is taken into a new arena,
+    /// allocated by [`Global`] with capacity of the moved bytes exactly.
+    /// Returns an empty arena if the arena is already shorter than the mark.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn split_off(&mut self, mark: Mark) -> StringArena {
+        let start = mark.0.min(self.len());
+        let sa = StringArena::with_capacity(self.len() - start);
+        if start < self.len() {
+            sa.copy_from(self, start, self.len()).unwrap();
+            sa.record(0);
+            self.discard(start);
+        }
+        sa
+    }
+
+    /// Discard the string added most recently.
+    /// Returns whether anything is discarded. Only one addition can be
+    /// undone, subsequent calls have no effect until next add.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn pop_last(&mut self) -> bool {
+        let last = self.last.get();
+        if last == self.len() {
+            return false;
+        }
+        self.discard(last);
+        true
+    }
+
+    /// Renew the arena for future usage.
+    /// This is safe because all string refs associated to this arena
+    /// must be dropped before this method call.
+    #[inline]
+    pub fn renew(mut self) -> Self {
+        self.clear();
+        self
+    }
+}
+
+/// Returns a dangling pointer of given alignment, for empty buffer.
+#[inline]
+fn dangling(align: usize) -> NonNull<u8> {
+    // SAFETY:
+    //
+    // Alignment is never zero.
+    unsafe { NonNull::new_unchecked(core::ptr::without_provenance_mut(align)) }
+}
+
+impl<A: BackingAlloc + Clone> Clone for StringArena<A> {
+    /// Copy the arena with the same capacity and written bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    fn clone(&self) -> Self {
+        let mut sa = Self::try_with_capacity_aligned_in(self.cap, self.align, self.alloc.clone())
+            .unwrap();
+        // SAFETY:
+        //
+        // The new buffer has the same capacity, and is not shared.
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.ptr.as_ptr(), sa.ptr.as_ptr(), self.len());
+        }
+        sa.idx.set(self.len());
+        sa.last.set(self.last.get());
+        sa.typed = UnsafeCell::new(self.typed_ref().to_vec());
+        sa.stats.set(self.stats.get());
+        sa.generation = self.generation;
+        sa.observer = self.observer.clone();
+        sa
+    }
+}
+
+impl<A: BackingAlloc> fmt::Debug for StringArena<A> {
+    /// Print usage of the arena. String boundaries are not recorded, see
+    /// [`IndexedStringArena`](crate::IndexedStringArena) for printing
+    /// stored strings.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StringArena")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<A: BackingAlloc, B: BackingAlloc> PartialEq<StringArena<B>> for StringArena<A> {
+    /// Compare written bytes of two arenas, regardless of capacity.
+    /// Typed values are compared by their regions only, since they may
+    /// contain uninitialized padding.
+    #[inline]
+    fn eq(&self, other: &StringArena<B>) -> bool {
+        if self.len() != other.len() || self.typed_ref() != other.typed_ref() {
+            return false;
+        }
+        // SAFETY:
+        //
+        // Both arenas have the same length and typed regions, so untyped
+        // regions are within written bytes of both, and initialized.
+        self.untyped()
+            .all(|(a, b)| unsafe { self.bytes_unchecked(a, b) == other.bytes_unchecked(a, b) })
+    }
+}
+
+impl<A: BackingAlloc> Eq for StringArena<A> {}
+
+impl<A: BackingAlloc> Hash for StringArena<A> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        self.typed_ref().hash(state);
+        for (a, b) in self.untyped() {
+            // SAFETY:
+            //
+            // Untyped regions are within written bytes and initialized.
+            state.write(unsafe { self.bytes_unchecked(a, b) });
+        }
+    }
+}
+
+impl<A: BackingAlloc, T: AsRef<str>> Extend<T> for StringArena<A> {
+    /// Add all strings into current arena, growing capacity on demand.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for s in it {
+            let s = s.as_ref();
+            self.reserve(s.len()).unwrap();
+            self.add(s).unwrap();
+        }
+    }
+}
+
+impl<T: AsRef<str>> FromIterator<T> for StringArena {
+    /// Create a new string arena with capacity of all strings exactly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Self {
+        let strs: Vec<T> = it.into_iter().collect();
+        let cap = strs.iter().map(|s| s.as_ref().len()).sum();
+        let mut sa = StringArena::with_capacity(cap);
+        sa.extend(strs);
+        sa
+    }
+}
+
+impl<A: BackingAlloc> Drop for StringArena<A> {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(budget) = &self.budget {
+            budget.release(self.len());
+        }
+        if self.cap != 0 {
+            // SAFETY:
+            //
+            // The pointer is allocated with the same layout in constructor
+            // or the last reallocation.
+            unsafe {
+                let layout = Layout::from_size_align_unchecked(self.cap, self.align);
+                self.alloc.deallocate(self.ptr, layout);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nightly-allocator")]
+// SAFETY:
+//
+// Allocated regions are never reused until the arena is cleared, which
+// requires that no allocation is alive.
+unsafe impl<A: BackingAlloc> core::alloc::Allocator for StringArena<A> {
+    #[inline]
+    fn allocate(
+        &self,
+        layout: Layout,
+    ) -> core::result::Result<NonNull<[u8]>, core::alloc::AllocError> {
+        if layout.size() == 0 {
+            return Ok(NonNull::slice_from_raw_parts(dangling(layout.align()), 0));
+        }
+        self.alloc_typed(layout)
+            .map(|ptr| NonNull::slice_from_raw_parts(ptr, layout.size()))
+            .map_err(|_| core::alloc::AllocError)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+        // Memory is reclaimed all at once when the arena is cleared or dropped.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_arena() {
+        let sa = StringArena::with_capacity(12);
+        assert_eq!(sa.len(), 0);
+        assert!(sa.is_empty());
+        assert_eq!(sa.capacity(), 12);
+        let s1 = sa.add("hello").unwrap();
+        assert_eq!(s1, "hello");
+        let s2 = sa.add("world").unwrap();
+        assert_eq!(s2, "world");
+        assert!(sa.add("rust").is_err());
+        let sa2 = sa.renew();
+        assert!(sa2.is_empty());
+    }
+
+    #[test]
+    fn test_remaining() {
+        let sa = StringArena::with_capacity(8);
+        assert_eq!(sa.remaining(), 8);
+        sa.add("hello").unwrap();
+        assert_eq!(sa.remaining(), 3);
+        assert!(sa.can_fit(3));
+        assert!(!sa.can_fit(4));
+        assert!(matches!(sa.add("rust"), Err(Error::ExceedsCapacity(1))));
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut sa = StringArena::with_capacity(0);
+        sa.reserve(5).unwrap();
+        assert_eq!(sa.capacity(), 5);
+        sa.add("hello").unwrap();
+        sa.reserve(1).unwrap();
+        assert_eq!(sa.capacity(), 10);
+        sa.grow_to(4).unwrap();
+        assert_eq!(sa.capacity(), 10);
+        sa.grow_to(12).unwrap();
+        assert_eq!(sa.add(" world!").unwrap(), " world!");
+        assert_eq!(sa.len(), 12);
+        let mut buf = [0u8; 4];
+        let mut sa = StringArena::from_buffer(&mut buf);
+        assert!(sa.reserve(8).is_err());
+        assert_eq!(sa.capacity(), 4);
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut sa = StringArena::with_capacity(16);
+        sa.add("hello").unwrap();
+        sa.shrink_to_fit().unwrap();
+        assert_eq!(sa.capacity(), 5);
+        assert!(sa.add("!").is_err());
+        assert_eq!(unsafe { sa.slice_unchecked(0, 5) }, "hello");
+        sa.clear();
+        sa.shrink_to_fit().unwrap();
+        assert_eq!(sa.capacity(), 0);
+    }
+
+    #[test]
+    fn test_pop_last() {
+        let mut sa = StringArena::with_capacity(32);
+        assert!(!sa.pop_last());
+        sa.add("hello").unwrap();
+        sa.add_fmt(format_args!("{}-{}", 1, 2)).unwrap();
+        assert!(sa.pop_last());
+        assert_eq!(sa.len(), 5);
+        assert!(!sa.pop_last());
+        let mut w = sa.writer();
+        w.write_str("dropped").unwrap();
+        drop(w);
+        assert!(!sa.pop_last());
+        assert_eq!(sa.len(), 5);
+    }
+
+    #[test]
+    fn test_add_all() {
+        let mut sa = StringArena::with_capacity(16);
+        sa.add("a").unwrap();
+        let res = sa.add_all(["hello", "", "world"]).unwrap();
+        assert_eq!(res, vec!["hello", "", "world"]);
+        assert!(matches!(sa.add_all(["rust", "lang"]), Err(Error::ExceedsCapacity(3))));
+        assert_eq!(sa.len(), 11);
+        let mut out = [""; 2];
+        sa.add_all_to(&["ab", "cd"], &mut out).unwrap();
+        assert_eq!(out, ["ab", "cd"]);
+        assert!(sa.pop_last());
+        assert_eq!(sa.len(), 11);
+    }
+
+    #[test]
+    fn test_extend_and_from_iter() {
+        let mut sa: StringArena = ["hello", " ", "world"].into_iter().collect();
+        assert_eq!(sa.capacity(), 11);
+        assert_eq!(sa.len(), 11);
+        sa.extend(vec![String::from("!"), String::from("?")]);
+        assert_eq!(sa.len(), 13);
+        assert_eq!(unsafe { sa.slice_unchecked(0, 13) }, "hello world!?");
+    }
+
+    #[test]
+    fn test_clone() {
+        let sa = StringArena::with_capacity(16);
+        sa.add("hello").unwrap();
+        let mut sa2 = sa.clone();
+        sa.add("world").unwrap();
+        assert_eq!(sa2.capacity(), 16);
+        assert_eq!(sa2.len(), 5);
+        assert_eq!(sa2.add("rust").unwrap(), "rust");
+        assert_eq!(unsafe { sa2.slice_unchecked(0, 9) }, "hellorust");
+        assert!(sa2.pop_last());
+        assert!(StringArena::with_capacity(0).clone().is_empty());
+    }
+
+    #[test]
+    fn test_eq_and_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |sa: &StringArena| {
+            let mut h = DefaultHasher::new();
+            sa.hash(&mut h);
+            h.finish()
+        };
+        let sa1 = StringArena::with_capacity(8);
+        let sa2 = StringArena::with_capacity(16);
+        sa1.add("hello").unwrap();
+        sa2.add("hel").unwrap();
+        assert!(sa1 != sa2);
+        sa2.add("lo").unwrap();
+        assert!(sa1 == sa2);
+        assert_eq!(hash(&sa1), hash(&sa2));
+        let mut buf = *b"hello";
+        let sa3 = StringArena::from_buffer(&mut buf);
+        sa3.add("hello").unwrap();
+        assert!(sa1 == sa3);
+    }
+
+    #[test]
+    fn test_eq_and_hash_typed() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |sa: &StringArena| {
+            let mut h = DefaultHasher::new();
+            sa.hash(&mut h);
+            h.finish()
+        };
+        let mut sa = StringArena::with_capacity(32);
+        sa.add("ab").unwrap();
+        // A value with uninitialized padding, never read as bytes.
+        sa.alloc((1u8, 2u32));
+        sa.add("cd").unwrap();
+        assert_eq!(sa.typed_ref(), &[(2, 12)]);
+        assert!(sa == sa);
+        let sa2 = sa.clone();
+        assert!(sa == sa2);
+        assert_eq!(hash(&sa), hash(&sa2));
+        let other = StringArena::with_capacity(32);
+        other.add("ab").unwrap();
+        other.add_bytes([0u8; 10]).unwrap();
+        other.add("cd").unwrap();
+        assert!(sa != other);
+        let sa3 = sa.split_off(Mark(5));
+        assert_eq!(sa3.typed_ref(), &[(0, 7)]);
+        assert_eq!(unsafe { sa3.slice_unchecked(7, 9) }, "cd");
+        assert!(sa3 == sa3.clone());
+        assert_eq!(sa.typed_ref(), &[(2, 5)]);
+        sa.truncate_to(Mark(2));
+        assert!(sa.typed_ref().is_empty());
+        let plain = StringArena::with_capacity(2);
+        plain.add("ab").unwrap();
+        assert!(sa == plain);
+        assert_eq!(hash(&sa), hash(&plain));
+    }
+
+    #[test]
+    fn test_append_arena() {
+        let mut sa = StringArena::with_capacity(8);
+        sa.add("hello").unwrap();
+        let other = StringArena::with_capacity(4);
+        other.add("abc").unwrap();
+        sa.append_arena(&other).unwrap();
+        assert_eq!(unsafe { sa.slice_unchecked(0, 8) }, "helloabc");
+        assert!(sa.append_arena(&other).is_err());
+        assert!(sa.pop_last());
+        assert_eq!(sa.len(), 5);
+    }
+
+    #[test]
+    fn test_append_arena_typed() {
+        let mut sa = StringArena::with_capacity(32);
+        sa.add("a").unwrap();
+        let other = StringArena::with_capacity(16);
+        other.add("xy").unwrap();
+        other.add_value((1u8, 2u16)).unwrap();
+        other.add("z").unwrap();
+        sa.append_arena(&other).unwrap();
+        assert_eq!(sa.len(), 1 + other.len());
+        assert_eq!(sa.typed_ref(), &[(3, 7)]);
+        assert_eq!(unsafe { sa.slice_unchecked(0, 3) }, "axy");
+        assert_eq!(unsafe { sa.slice_unchecked(7, 8) }, "z");
+        assert!(sa == sa);
+        let mut sa2 = StringArena::with_capacity(32);
+        sa2.add("a").unwrap();
+        sa2.append_arena(&other).unwrap();
+        assert!(sa == sa2);
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut sa = StringArena::with_capacity(16);
+        sa.add("hello").unwrap();
+        let mark = sa.checkpoint();
+        sa.add("world").unwrap();
+        let sa2 = sa.split_off(mark);
+        assert_eq!(sa.len(), 5);
+        assert_eq!(sa2.capacity(), 5);
+        assert_eq!(unsafe { sa2.slice_unchecked(0, 5) }, "world");
+        assert_eq!(sa2.stats().strings, 1);
+        let sa3 = sa.split_off(mark);
+        assert!(sa3.is_empty());
+        assert_eq!(sa3.stats(), Stats::default());
+        assert!(sa.split_off(Mark(10)).is_empty());
+        assert_eq!(sa.len(), 5);
+    }
+
+    #[test]
+    fn test_raw_parts() {
+        struct Noop;
+        impl Observer for Noop {}
+        let observer: Arc<dyn Observer> = Arc::new(Noop);
+        let budget = MemoryBudget::new(16);
+        let mut sa = StringArena::try_with_capacity_aligned_in(16, 64, Global).unwrap();
+        sa.set_observer(Some(observer.clone()));
+        sa.set_budget(Some(budget.clone())).unwrap();
+        sa.add("hello").unwrap();
+        let (ptr, len, cap, align) = sa.into_raw_parts();
+        assert_eq!((len, cap, align), (5, 16, 64));
+        assert_eq!(Arc::strong_count(&observer), 1);
+        assert_eq!(budget.used(), 0);
+        let sa = unsafe { StringArena::from_raw_parts(ptr, len, cap, align) };
+        assert_eq!(unsafe { sa.slice_unchecked(0, 5) }, "hello");
+        assert_eq!(sa.alignment(), 64);
+    }
+
+    #[test]
+    fn test_add_or_heap() {
+        let sa = StringArena::with_capacity(8);
+        assert!(matches!(sa.add_or_heap("hello"), Cow::Borrowed("hello")));
+        assert!(matches!(sa.add_or_heap("world"), Cow::Owned(s) if s == "world"));
+        assert!(matches!(sa.add_or_heap("abc"), Cow::Borrowed("abc")));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut sa = StringArena::with_capacity(32);
+        assert_eq!(sa.stats().average_len(), 0.0);
+        sa.add("hello").unwrap();
+        sa.add_fmt(format_args!("{}{}", "a", "bc")).unwrap();
+        sa.add_concat(["x", "y", "z", "w"]).unwrap();
+        assert!(sa.add("x".repeat(32)).is_err());
+        assert!(sa.add_all(["abcdefghijk"; 2]).is_err());
+        sa.pop_last();
+        let stats = sa.stats();
+        assert_eq!(sa.string_count(), 3);
+        assert_eq!(stats.bytes, 12);
+        assert_eq!(stats.longest, 5);
+        assert_eq!(stats.average_len(), 4.0);
+        assert_eq!(stats.peak_len, 12);
+        assert_eq!(stats.failures, 2);
+        assert_eq!(stats.bytes_rejected, 54);
+        sa.clear();
+        assert_eq!(sa.stats(), Stats::default());
+    }
+
+    #[test]
+    fn test_stats_wasted_bytes() {
+        let mut sa = StringArena::try_with_capacity_aligned_in(32, 8, Global).unwrap();
+        sa.add("a").unwrap();
+        sa.add_value(1u64).unwrap();
+        sa.add_value(2u64).unwrap();
+        sa.add("b").unwrap();
+        sa.add_slice(&[3u32]).unwrap();
+        assert_eq!(sa.len(), 32);
+        assert_eq!(sa.stats().wasted_bytes, 10);
+        assert!(sa.add_value(4u64).is_err());
+        assert_eq!(sa.stats().wasted_bytes, 10);
+        sa.clear();
+        assert_eq!(sa.stats().wasted_bytes, 0);
+    }
+
+    #[cfg(feature = "poison")]
+    #[test]
+    fn test_poison() {
+        let mut sa = StringArena::with_capacity(8);
+        sa.add("hello").unwrap();
+        sa.clear();
+        let bs = unsafe { core::slice::from_raw_parts(sa.as_mut_ptr(), 5) };
+        assert!(bs.iter().all(|&b| b == POISON));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut sa = StringArena::with_capacity(16);
+        sa.add("hello").unwrap();
+        let mark = sa.checkpoint();
+        assert_eq!(mark.offset(), 5);
+        sa.add("world").unwrap();
+        sa.truncate_to(mark);
+        assert_eq!(sa.len(), 5);
+        let mark2 = sa.checkpoint();
+        sa.truncate_to(Mark(0));
+        sa.truncate_to(mark2);
+        assert!(sa.is_empty());
+    }
+
+    #[test]
+    fn test_add_fmt() {
+        let sa = StringArena::with_capacity(16);
+        assert_eq!(sa.add_fmt(format_args!("{}+{}", 1, 2)).unwrap(), "1+2");
+        assert_eq!(sa.add_fmt(format_args!("const")).unwrap(), "const");
+        assert_eq!(crate::arena_write!(sa, "{:>4}", 7).unwrap(), "   7");
+        assert!(crate::arena_write!(sa, "{}", "overflow").is_err());
+        assert_eq!(sa.len(), 12);
+
+        struct Failing;
+        impl fmt::Display for Failing {
+            fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+        let res = sa.add_fmt(format_args!("a{}", Failing));
+        assert!(matches!(res, Err(Error::FormatFailed)));
+        assert_eq!(sa.len(), 12);
+    }
+
+    #[test]
+    fn test_add_concat() {
+        let sa = StringArena::with_capacity(24);
+        let s = sa.add_concat(["module", "::", "Type", "::", "method"]).unwrap();
+        assert_eq!(s, "module::Type::method");
+        assert_eq!(sa.add_concat(Vec::<String>::new()).unwrap(), "");
+        assert!(sa.add_concat(["ab", "cd", "ef"]).is_err());
+        assert_eq!(sa.len(), 20);
+    }
+
+    #[test]
+    fn test_add_join() {
+        let sa = StringArena::with_capacity(24);
+        assert_eq!(sa.add_join(["a", "b", "c"], ", ").unwrap(), "a, b, c");
+        assert_eq!(sa.add_join(["usr", "lib"], "/").unwrap(), "usr/lib");
+        assert_eq!(sa.add_join(["only"], ",").unwrap(), "only");
+        assert_eq!(sa.add_join(Vec::<String>::new(), ",").unwrap(), "");
+        assert!(sa.add_join(["ab", "cd"], "---").is_err());
+        assert_eq!(sa.len(), 18);
+    }
+
+    #[test]
+    fn test_add_bytes() {
+        let sa = StringArena::with_capacity(8);
+        let b1 = sa.add_bytes([0xff, 0x00, 0xfe]).unwrap();
+        let s2 = sa.add("abc").unwrap();
+        assert_eq!(b1, &[0xff, 0x00, 0xfe]);
+        assert_eq!(s2, "abc");
+        assert!(sa.add_bytes(b"xyz").is_err());
+        assert_eq!(sa.len(), 6);
+    }
+
+    #[test]
+    fn test_add_cstr() {
+        let sa = StringArena::with_capacity(8);
+        let c = sa.add_cstr("hello").unwrap();
+        assert_eq!(c.to_bytes_with_nul(), b"hello\0");
+        assert!(matches!(sa.add_cstr("a\0b"), Err(Error::InteriorNul(1))));
+        assert!(sa.add_cstr("ab").is_err());
+        assert_eq!(sa.add_cstr("a").unwrap().to_bytes(), b"a");
+        assert_eq!(sa.len(), 8);
+    }
+
+    #[test]
+    fn test_add_slice() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Token {
+            kind: u32,
+            len: u32,
+        }
+        let sa = StringArena::with_capacity(64);
+        let s = sa.add("abc").unwrap();
+        let v = sa.add_value(0x1234_5678u64).unwrap();
+        assert_eq!(*v, 0x1234_5678);
+        assert_eq!(v as *const u64 as usize % align_of::<u64>(), 0);
+        let ts = [Token { kind: 1, len: 3 }, Token { kind: 2, len: 0 }];
+        let ts2 = sa.add_slice(&ts).unwrap();
+        assert_eq!(ts2, &ts);
+        assert_eq!(sa.add_slice::<u32>(&[]).unwrap(), &[] as &[u32]);
+        assert!(sa.add_slice(&[0u64; 8]).is_err());
+        assert_eq!(s, "abc");
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut sa = StringArena::with_capacity(8);
+        sa.add("hello").unwrap();
+        assert!(sa.add("world").is_err());
+        sa.clear();
+        assert!(sa.is_empty());
+        assert_eq!(sa.add("world").unwrap(), "world");
+    }
+
+    #[test]
+    fn test_backing_alloc() {
+        use std::ptr::NonNull;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct Counting(AtomicUsize);
+        unsafe impl BackingAlloc for Counting {
+            fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+                self.0.fetch_add(layout.size(), Ordering::Relaxed);
+                Global.allocate(layout)
+            }
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.0.fetch_sub(layout.size(), Ordering::Relaxed);
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        let counting = Counting::default();
+        let sa = StringArena::with_capacity_in(16, &counting);
+        assert_eq!(sa.add("hello").unwrap(), "hello");
+        assert_eq!(sa.allocator().0.load(Ordering::Relaxed), 16);
+        drop(sa);
+        assert_eq!(counting.0.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_from_buffer() {
+        let mut buf = [0u8; 8];
+        {
+            let sa = StringArena::from_buffer(&mut buf);
+            assert_eq!(sa.capacity(), 8);
+            assert_eq!(sa.add("hello").unwrap(), "hello");
+            assert!(sa.add("world").is_err());
+            assert!(StringArena::try_with_capacity_in(1, sa.allocator()).is_err());
+        }
+        assert_eq!(&buf[..5], b"hello");
+        let sa = StringArena::from_buffer(&mut []);
+        assert_eq!(sa.add("").unwrap(), "");
+    }
+
+    #[test]
+    fn test_try_with_capacity() {
+        let sa = StringArena::try_with_capacity(0).unwrap();
+        assert_eq!(sa.capacity(), 0);
+        assert_eq!(sa.add("").unwrap(), "");
+        assert!(sa.add("a").is_err());
+        assert!(matches!(
+            StringArena::try_with_capacity(usize::MAX),
+            Err(Error::AllocFailed(usize::MAX))
+        ));
+    }
+
+    #[cfg(feature = "nightly-allocator")]
+    #[test]
+    fn test_allocator() {
+        let sa = StringArena::with_capacity(64);
+        let mut v: Vec<u32, &StringArena> = Vec::new_in(&sa);
+        v.extend([1, 2, 3]);
+        let b = Box::new_in(7u64, &sa);
+        assert_eq!(&v[..], &[1, 2, 3]);
+        assert_eq!(*b, 7);
+        assert!(!sa.is_empty());
+        assert!(Vec::<u8, _>::try_with_capacity_in(128, &sa).is_err());
+    }
+}
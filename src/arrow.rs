@@ -0,0 +1,51 @@
+//! Export of [`IndexedStringArena`] in layout of Apache Arrow.
+use crate::IndexedStringArena;
+
+/// ArrowBuffers is the layout of Arrow `LargeUtf8` array, borrowed
+/// from [`IndexedStringArena`] without copying.
+///
+/// The i-th string spans `values[offsets[i]..offsets[i+1]]`. Both
+/// buffers can be wrapped into a `LargeStringArray` directly.
+#[derive(Debug, Clone, Copy)]
+pub struct ArrowBuffers<'a> {
+    /// Offsets of all strings with a leading zero.
+    pub offsets: &'a [i64],
+    /// Bytes of all strings.
+    pub values: &'a [u8],
+}
+
+impl IndexedStringArena {
+    /// Returns buffers of all strings in layout of Arrow `LargeUtf8` array.
+    /// The exclusive borrow guarantees no string is added while
+    /// buffers are alive.
+    #[inline]
+    pub fn arrow_buffers(&mut self) -> ArrowBuffers<'_> {
+        let offsets = self.offsets_mut();
+        // SAFETY:
+        //
+        // usize and i64 have same layout on 64-bit platforms, and offsets
+        // never exceed isize::MAX.
+        let offsets =
+            unsafe { core::slice::from_raw_parts(offsets.as_ptr() as *const i64, offsets.len()) };
+        ArrowBuffers {
+            offsets,
+            values: self.values(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::IndexedStringArena;
+
+    #[test]
+    fn test_arrow_buffers() {
+        let mut ia = IndexedStringArena::with_capacity(16);
+        for s in ["a", "", "bcd"] {
+            ia.add(s).unwrap();
+        }
+        let bufs = ia.arrow_buffers();
+        assert_eq!(bufs.offsets, &[0, 1, 1, 4]);
+        assert_eq!(bufs.values, b"abcd");
+    }
+}
@@ -0,0 +1,132 @@
+use alloc::alloc::{alloc, dealloc, Layout};
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+/// BackingAlloc allocates the backing buffer of an arena.
+///
+/// It is a minimal allocator interface available on stable, so an arena
+/// can be backed by a custom allocator, a pool or a static region.
+/// The arena allocates its buffer once on creation and deallocates it
+/// on drop.
+///
+/// # Safety
+///
+/// Memory returned by [`allocate`](Self::allocate) must be valid for
+/// reads and writes of `layout.size()` bytes, aligned to `layout.align()`,
+/// and must not be used by others until it is deallocated.
+pub unsafe trait BackingAlloc {
+    /// Allocate memory of given layout, which always has non-zero size.
+    /// Returns `None` if the allocation fails.
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// Deallocate memory returned by [`allocate`](Self::allocate).
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be allocated by this allocator with same layout.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Hint that given region is not used any more, so its pages can be
+    /// returned to OS. The region stays valid but its contents may be lost.
+    /// It does nothing by default.
+    ///
+    /// # Safety
+    ///
+    /// The region must be within memory allocated by this allocator, and
+    /// not referenced.
+    #[inline]
+    unsafe fn purge(&self, _ptr: NonNull<u8>, _len: usize) {}
+}
+
+/// Global is the backing allocator which uses the global allocator
+/// registered by `#[global_allocator]`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Global;
+
+// SAFETY:
+//
+// Global allocator satisfies the contract.
+unsafe impl BackingAlloc for Global {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        // SAFETY:
+        //
+        // The layout has non-zero size.
+        NonNull::new(unsafe { alloc(layout) })
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        dealloc(ptr.as_ptr(), layout)
+    }
+}
+
+// SAFETY:
+//
+// Delegates to the referenced allocator.
+unsafe impl<A: BackingAlloc + ?Sized> BackingAlloc for &A {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        (**self).allocate(layout)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn purge(&self, ptr: NonNull<u8>, len: usize) {
+        (**self).purge(ptr, len)
+    }
+}
+
+/// BufferAlloc is the backing allocator over a caller-provided buffer.
+///
+/// It is created by [`StringArena::from_buffer`](crate::StringArena::from_buffer),
+/// and hands out the whole buffer at most once.
+#[derive(Debug)]
+pub struct BufferAlloc<'a> {
+    ptr: NonNull<u8>,
+    len: usize,
+    taken: Cell<bool>,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> BufferAlloc<'a> {
+    #[inline]
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        BufferAlloc {
+            // SAFETY:
+            //
+            // Slice pointer is never null.
+            ptr: unsafe { NonNull::new_unchecked(buf.as_mut_ptr()) },
+            len: buf.len(),
+            taken: Cell::new(false),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// SAFETY:
+//
+// The buffer is exclusively borrowed and handed out at most once.
+unsafe impl BackingAlloc for BufferAlloc<'_> {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        if self.taken.get()
+            || layout.size() > self.len
+            || self.ptr.as_ptr().align_offset(layout.align()) != 0
+        {
+            return None;
+        }
+        self.taken.set(true);
+        Some(self.ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+        // The buffer is owned by caller.
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{Error, Result};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// MemoryBudget is a limit of total bytes shared by multiple arenas.
+///
+/// Arenas registered by [`StringArena::set_budget`](crate::StringArena::set_budget)
+/// charge bytes added to the budget, and release them when the bytes are
+/// discarded or the arena is dropped. Adding fails with
+/// [`Error::BudgetExceeded`] if the limit would be crossed.
+/// Clones of the handle share the same budget.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Create a new budget with given limit of total bytes.
+    #[inline]
+    pub fn new(limit: usize) -> Self {
+        MemoryBudget {
+            inner: Arc::new(Inner {
+                limit,
+                used: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Returns the limit of total bytes.
+    #[inline]
+    pub fn limit(&self) -> usize {
+        self.inner.limit
+    }
+
+    /// Returns bytes charged by all registered arenas.
+    /// The value may be stale if other threads are using the budget.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.inner.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns bytes which can still be charged.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.limit().saturating_sub(self.used())
+    }
+
+    /// Charge given bytes to the budget.
+    /// Fails with bytes exceeding the limit, and nothing is charged.
+    #[inline]
+    pub(crate) fn charge(&self, len: usize) -> Result<()> {
+        let limit = self.inner.limit;
+        self.inner
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(len).filter(|&n| n <= limit)
+            })
+            .map(|_| ())
+            .map_err(|used| Error::BudgetExceeded(used.saturating_add(len) - limit))
+    }
+
+    /// Release given bytes charged before.
+    #[inline]
+    pub(crate) fn release(&self, len: usize) {
+        self.inner.used.fetch_sub(len, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StringArena;
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = MemoryBudget::new(12);
+        let mut sa1 = StringArena::with_capacity(16);
+        let mut sa2 = StringArena::with_capacity(16);
+        sa1.add("hello").unwrap();
+        sa1.set_budget(Some(budget.clone())).unwrap();
+        sa2.set_budget(Some(budget.clone())).unwrap();
+        sa2.add("world").unwrap();
+        assert_eq!(budget.used(), 10);
+        assert!(matches!(sa1.add("abc"), Err(Error::BudgetExceeded(1))));
+        assert!(sa2.add_all(["a", "bc"]).is_err());
+        assert_eq!(sa2.len(), 5);
+        sa1.add_fmt(format_args!("{}", 42)).unwrap();
+        assert_eq!(budget.remaining(), 0);
+        sa1.clear();
+        assert_eq!(budget.used(), 5);
+        drop(sa2);
+        assert_eq!(budget.used(), 0);
+        sa1.add("hello").unwrap();
+        sa1.set_budget(None).unwrap();
+        assert_eq!(budget.used(), 0);
+    }
+}
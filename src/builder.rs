@@ -0,0 +1,224 @@
+use crate::{
+    BackingAlloc, Global, GrowableStringArena, GrowthPolicy, MemoryBudget, Observer, Result,
+    StringArena,
+};
+use alloc::sync::Arc;
+
+/// StringArenaBuilder configures and creates a [`StringArena`], returned
+/// by [`StringArena::builder`].
+#[derive(Clone)]
+pub struct StringArenaBuilder<A: BackingAlloc = Global> {
+    capacity: usize,
+    alignment: usize,
+    zero_init: bool,
+    observer: Option<Arc<dyn Observer>>,
+    budget: Option<MemoryBudget>,
+    alloc: A,
+}
+
+impl StringArena {
+    /// Returns a builder of string arena, with zero capacity, byte
+    /// alignment and [`Global`] allocator by default.
+    #[inline]
+    pub fn builder() -> StringArenaBuilder {
+        StringArenaBuilder {
+            capacity: 0,
+            alignment: 1,
+            zero_init: false,
+            observer: None,
+            budget: None,
+            alloc: Global,
+        }
+    }
+}
+
+impl<A: BackingAlloc> StringArenaBuilder<A> {
+    /// Set the capacity of the arena.
+    #[inline]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set the alignment of the buffer, e.g. 64 for cache line or 4096
+    /// for page, which must be a power of two.
+    #[inline]
+    pub fn alignment(mut self, alignment: usize) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Set whether the whole buffer is zeroed on creation, so its pages
+    /// are populated up front.
+    #[inline]
+    pub fn zero_init(mut self, zero_init: bool) -> Self {
+        self.zero_init = zero_init;
+        self
+    }
+
+    /// Set the observer notified of events of the arena.
+    #[inline]
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Set the memory budget the arena is registered with.
+    #[inline]
+    pub fn budget(mut self, budget: MemoryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Set the backing allocator of the arena.
+    #[inline]
+    pub fn alloc<B: BackingAlloc>(self, alloc: B) -> StringArenaBuilder<B> {
+        StringArenaBuilder {
+            capacity: self.capacity,
+            alignment: self.alignment,
+            zero_init: self.zero_init,
+            observer: self.observer,
+            budget: self.budget,
+            alloc,
+        }
+    }
+
+    /// Create the string arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn build(self) -> StringArena<A> {
+        self.try_build().unwrap()
+    }
+
+    /// Try to create the string arena.
+    /// Returns error if the alignment is not a power of two, or the
+    /// allocation fails.
+    #[inline]
+    pub fn try_build(self) -> Result<StringArena<A>> {
+        let mut sa =
+            StringArena::try_with_capacity_aligned_in(self.capacity, self.alignment, self.alloc)?;
+        if self.zero_init {
+            // SAFETY:
+            //
+            // The buffer is newly allocated with given capacity.
+            unsafe { core::ptr::write_bytes(sa.as_mut_ptr(), 0, sa.capacity()) };
+        }
+        // Nothing is written, so nothing is charged.
+        sa.set_budget(self.budget)?;
+        if let Some(observer) = &self.observer {
+            observer.on_create(sa.capacity());
+        }
+        sa.set_observer(self.observer);
+        Ok(sa)
+    }
+}
+
+impl StringArenaBuilder {
+    /// Set the growth policy, switching to build a
+    /// [`GrowableStringArena`] whose first chunk has the capacity, and
+    /// all chunks have the other settings configured so far.
+    #[inline]
+    pub fn growth(self, growth: GrowthPolicy) -> GrowableArenaBuilder {
+        GrowableArenaBuilder {
+            bld: self,
+            growth,
+            max_chunk_size: usize::MAX,
+        }
+    }
+}
+
+/// GrowableArenaBuilder configures and creates a [`GrowableStringArena`],
+/// returned by [`StringArenaBuilder::growth`].
+#[derive(Clone)]
+pub struct GrowableArenaBuilder {
+    bld: StringArenaBuilder,
+    growth: GrowthPolicy,
+    max_chunk_size: usize,
+}
+
+impl GrowableArenaBuilder {
+    /// Set the maximum size of chunks allocated by the growth policy.
+    #[inline]
+    pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.max_chunk_size = max_chunk_size;
+        self
+    }
+
+    /// Create the growable string arena.
+    /// Chunks are allocated lazily, so any allocation failure is reported
+    /// on add.
+    #[inline]
+    pub fn build(self) -> GrowableStringArena {
+        GrowableStringArena::with_template(self.bld.capacity, self.bld)
+            .growth(self.growth)
+            .max_chunk_size(self.max_chunk_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_string_arena_builder() {
+        let budget = MemoryBudget::new(4);
+        let sa = StringArena::builder()
+            .capacity(16)
+            .zero_init(true)
+            .budget(budget.clone())
+            .build();
+        assert_eq!(sa.capacity(), 16);
+        assert!(matches!(sa.add("hello"), Err(Error::BudgetExceeded(1))));
+        assert_eq!(sa.add("rust").unwrap(), "rust");
+        assert_eq!(budget.used(), 4);
+        let mut buf = [0u8; 8];
+        let sa = StringArena::builder()
+            .capacity(8)
+            .alloc(crate::BufferAlloc::new(&mut buf))
+            .build();
+        assert_eq!(sa.add("world").unwrap(), "world");
+        assert!(StringArena::builder().capacity(usize::MAX).try_build().is_err());
+    }
+
+    #[test]
+    fn test_string_arena_builder_alignment() {
+        for align in [1, 64, 4096] {
+            let mut sa = StringArena::builder().capacity(8).alignment(align).build();
+            assert_eq!(sa.alignment(), align);
+            assert_eq!(sa.add("hello").unwrap().as_ptr() as usize % align, 0);
+            sa.reserve(100).unwrap();
+            assert_eq!(sa.as_mut_ptr() as usize % align, 0);
+            sa.clear();
+            sa.shrink_to_fit().unwrap();
+            assert_eq!(sa.as_mut_ptr() as usize % align, 0);
+            let sa2 = sa.clone();
+            assert_eq!(sa2.alignment(), align);
+        }
+        assert!(StringArena::builder().alignment(3).try_build().is_err());
+    }
+
+    #[test]
+    fn test_string_arena_builder_growth() {
+        let budget = MemoryBudget::new(12);
+        let ga = StringArena::builder()
+            .capacity(4)
+            .alignment(64)
+            .budget(budget.clone())
+            .growth(GrowthPolicy::Doubling)
+            .max_chunk_size(8)
+            .build();
+        assert_eq!(ga.chunk_size(), 4);
+        assert_eq!(ga.add("abcd").as_ptr() as usize % 64, 0);
+        assert_eq!(ga.add("abcd").as_ptr() as usize % 64, 0);
+        ga.add("abcd");
+        assert_eq!(ga.chunks(), 2);
+        assert_eq!(ga.capacity(), 12);
+        assert_eq!(budget.used(), 12);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ga.add("a")));
+        assert!(res.is_err());
+    }
+}
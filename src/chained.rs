@@ -0,0 +1,145 @@
+use crate::{BackingAlloc, Error, Global, GrowableStringArena, Result, StringArena};
+
+/// ChainedStringArena is a string arena which never runs out of capacity,
+/// by chaining follow-on chunks once its primary arena is full.
+///
+/// The primary arena can be capped at a size friendly to its backing
+/// allocator, e.g. a huge page, while overflow strings go to chunks of
+/// a separate, usually smaller, size.
+pub struct ChainedStringArena<A: BackingAlloc = Global> {
+    primary: StringArena<A>,
+    overflow: GrowableStringArena,
+}
+
+impl ChainedStringArena {
+    /// Create a new chained string arena with given capacity of primary
+    /// arena and size of follow-on chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize, chunk_size: usize) -> Self {
+        Self::new(StringArena::with_capacity(cap), chunk_size)
+    }
+}
+
+impl<A: BackingAlloc> ChainedStringArena<A> {
+    /// Create a new chained string arena over given primary arena.
+    /// Follow-on chunks of given size are allocated lazily on overflow.
+    #[inline]
+    pub fn new(primary: StringArena<A>, chunk_size: usize) -> Self {
+        ChainedStringArena {
+            primary,
+            overflow: GrowableStringArena::with_chunk_size(chunk_size),
+        }
+    }
+
+    /// Returns the primary arena.
+    #[inline]
+    pub fn primary(&self) -> &StringArena<A> {
+        &self.primary
+    }
+
+    /// Returns number of follow-on chunks allocated.
+    #[inline]
+    pub fn chunks(&self) -> usize {
+        self.overflow.chunks()
+    }
+
+    /// Returns bytes written of the primary arena and all chunks.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.primary.len() + self.overflow.len()
+    }
+
+    /// Returns whether the arena is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.primary.is_empty() && self.overflow.is_empty()
+    }
+
+    /// Returns total capacity of the primary arena and all chunks.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.primary.capacity() + self.overflow.capacity()
+    }
+
+    /// Add a string into current arena.
+    /// Returns the string ref.
+    /// The string goes to the primary arena if it fits, otherwise to
+    /// follow-on chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the primary arena fails for other reasons than capacity,
+    /// e.g. exceeding its memory budget, or allocation of a chunk fails.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> &str {
+        self.try_add(s).unwrap()
+    }
+
+    /// Try to add a string into current arena.
+    /// Returns the string ref if succeeds, or the error of the primary
+    /// arena if it fails for other reasons than capacity.
+    #[inline]
+    pub fn try_add<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        let s = s.as_ref();
+        match self.primary.add(s) {
+            Err(Error::ExceedsCapacity(_)) => Ok(self.overflow.add(s)),
+            res => res,
+        }
+    }
+
+    /// Clear the arena so its memory can be reused.
+    /// Only the most recently allocated chunk is retained.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.primary.clear();
+        self.overflow.clear();
+    }
+
+    /// Renew the arena for future usage.
+    /// Only the most recently allocated chunk is retained.
+    /// This is safe because all string refs associated to this arena
+    /// must be dropped before this method call.
+    #[inline]
+    pub fn renew(mut self) -> Self {
+        self.clear();
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chained_string_arena() {
+        let ca = ChainedStringArena::with_capacity(8, 4);
+        let s1 = ca.add("hello");
+        let s2 = ca.add("world");
+        let s3 = ca.add("abc");
+        assert_eq!(ca.chunks(), 1);
+        assert_eq!((s1, s2, s3), ("hello", "world", "abc"));
+        assert_eq!(ca.primary().len(), 8);
+        assert_eq!(ca.len(), 13);
+        assert_eq!(ca.capacity(), 13);
+        let ca = ca.renew();
+        assert!(ca.is_empty());
+        assert_eq!(ca.add("hello"), "hello");
+        assert_eq!(ca.chunks(), 1);
+    }
+
+    #[test]
+    fn test_chained_string_arena_budget() {
+        let mut primary = StringArena::with_capacity(8);
+        primary.set_budget(Some(crate::MemoryBudget::new(4))).unwrap();
+        let ca = ChainedStringArena::new(primary, 4);
+        assert_eq!(ca.try_add("abc").unwrap(), "abc");
+        assert!(matches!(ca.try_add("de"), Err(Error::BudgetExceeded(1))));
+        assert_eq!(ca.try_add("a long string").unwrap(), "a long string");
+        assert_eq!(ca.chunks(), 1);
+    }
+}
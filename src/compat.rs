@@ -0,0 +1,96 @@
+//! API surface mirroring [bumpalo](https://docs.rs/bumpalo), so code
+//! written against it can switch to [`StringArena`] with minimal churn.
+//!
+//! Unlike other methods of the arena, these methods panic if the arena
+//! exceeds capacity, same as bumpalo panics on allocation failure.
+//!
+//! Unlike bumpalo, shared refs are returned, because the arena can read
+//! its own contents, e.g. by [`get`](StringArena::get) or on clone.
+use crate::{BackingAlloc, StringArena};
+use core::alloc::Layout;
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Allocate a value in the arena and returns a ref to it.
+    /// The value is never dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena exceeds capacity.
+    #[inline]
+    pub fn alloc<T>(&self, val: T) -> &T {
+        let dst = self.alloc_typed(Layout::new::<T>()).unwrap().as_ptr() as *mut T;
+        // SAFETY:
+        //
+        // The destination is reserved for the value and properly aligned.
+        unsafe {
+            dst.write(val);
+            &*dst
+        }
+    }
+
+    /// Copy a string into the arena and returns a ref to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena exceeds capacity.
+    #[inline]
+    pub fn alloc_str(&self, src: &str) -> &str {
+        let len = src.len();
+        let dst = self.alloc_layout(Layout::for_value(src)).unwrap().as_ptr();
+        // SAFETY:
+        //
+        // The destination is reserved for the bytes, which are copied from
+        // valid UTF-8.
+        unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr(), dst, len);
+            core::str::from_utf8_unchecked(core::slice::from_raw_parts(dst, len))
+        }
+    }
+
+    /// Copy a slice into the arena and returns a ref to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena exceeds capacity.
+    #[inline]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &[T] {
+        let dst = self.alloc_typed(Layout::for_value(src)).unwrap().as_ptr() as *mut T;
+        // SAFETY:
+        //
+        // The destination is reserved for the slice and properly aligned.
+        unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr(), dst, src.len());
+            core::slice::from_raw_parts(dst, src.len())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Span, StringArena};
+
+    #[test]
+    fn test_bumpalo_compat() {
+        let sa = StringArena::with_capacity(64);
+        let s = sa.alloc_str("hello");
+        // Bytes returned are shared with other reads of the arena.
+        let r = sa.get(Span::new(0, 5)).unwrap();
+        assert_eq!(s, "hello");
+        assert_eq!(r, "hello");
+        let sa2 = sa.clone();
+        assert_eq!(sa2, sa);
+        assert_eq!(s, "hello");
+        let v = sa.alloc(core::cell::Cell::new(3u32));
+        v.set(v.get() + 1);
+        assert_eq!(v.get(), 4);
+        let vs = sa.alloc_slice_copy(&[1u16, 2, 3]);
+        assert_eq!(vs, &[1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bumpalo_compat_exceeds_capacity() {
+        let sa = StringArena::with_capacity(4);
+        sa.alloc_str("hello");
+    }
+}
@@ -0,0 +1,88 @@
+//! CRC-32 (IEEE) checksum, computed by slicing-by-8.
+
+/// Lookup tables, where `TABLES[k][b]` is the CRC of byte `b` followed by
+/// `k` zero bytes.
+const TABLES: [[u32; 256]; 8] = tables();
+
+const fn tables() -> [[u32; 256]; 8] {
+    let mut t = [[0u32; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+            j += 1;
+        }
+        t[0][i] = c;
+        i += 1;
+    }
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let c = t[k - 1][i];
+            t[k][i] = (c >> 8) ^ t[0][(c & 0xff) as usize];
+            i += 1;
+        }
+        k += 1;
+    }
+    t
+}
+
+/// Crc32 is a streaming CRC-32 checksum.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Crc32(!0)
+    }
+
+    /// Update the checksum with given bytes.
+    pub(crate) fn update(&mut self, bs: &[u8]) {
+        let mut c = self.0;
+        let mut chunks = bs.chunks_exact(8);
+        for chunk in &mut chunks {
+            let lo = c ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            c = TABLES[7][(lo & 0xff) as usize]
+                ^ TABLES[6][((lo >> 8) & 0xff) as usize]
+                ^ TABLES[5][((lo >> 16) & 0xff) as usize]
+                ^ TABLES[4][(lo >> 24) as usize]
+                ^ TABLES[3][chunk[4] as usize]
+                ^ TABLES[2][chunk[5] as usize]
+                ^ TABLES[1][chunk[6] as usize]
+                ^ TABLES[0][chunk[7] as usize];
+        }
+        for &b in chunks.remainder() {
+            c = (c >> 8) ^ TABLES[0][((c ^ b as u32) & 0xff) as usize];
+        }
+        self.0 = c;
+    }
+
+    /// Returns the checksum of all bytes updated.
+    #[inline]
+    pub(crate) fn finish(self) -> u32 {
+        !self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        let mut c = Crc32::new();
+        c.update(b"123456789");
+        assert_eq!(c.finish(), 0xcbf4_3926);
+        let bs: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let mut c1 = Crc32::new();
+        c1.update(&bs);
+        let mut c2 = Crc32::new();
+        bs.chunks(13).for_each(|c| c2.update(c));
+        assert_eq!(c1.finish(), c2.finish());
+        assert_eq!(Crc32::new().finish(), 0);
+    }
+}
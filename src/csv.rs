@@ -0,0 +1,261 @@
+//! Ingestion of CSV records, which requires feature `csv`.
+use crate::{BackingAlloc, Error, Result, StrId, StringArena, TrieInterner};
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::io::BufRead;
+
+/// CsvReader reads records of CSV from a reader, storing fields into an
+/// arena instead of owned strings.
+///
+/// Fields are separated by the delimiter, `,` by default, and may be
+/// quoted by `"`, with `""` inside quotes for a quote itself. Quoted fields
+/// can contain delimiters and line breaks. Blank lines are skipped.
+///
+/// The buffers of the reader are reused among records, so ingestion only
+/// allocates memory in the arena.
+pub struct CsvReader<R> {
+    reader: R,
+    delimiter: u8,
+    // Bytes of current record.
+    record: Vec<u8>,
+    // Unescaped quoted field.
+    field: String,
+    // Bytes consumed from the reader, and offset of current record in them.
+    input: usize,
+    offset: usize,
+}
+
+/// State of scanning bytes of a record for its end.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scan {
+    FieldStart,
+    Unquoted,
+    Quoted,
+    // A quote inside a quoted field, which either closes it or escapes
+    // the next quote.
+    QuoteInQuoted,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    /// Create a new CSV reader over the reader.
+    #[inline]
+    pub fn new(reader: R) -> Self {
+        CsvReader {
+            reader,
+            delimiter: b',',
+            record: Vec::new(),
+            field: String::new(),
+            input: 0,
+            offset: 0,
+        }
+    }
+
+    /// Set the delimiter between fields.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the delimiter is not ASCII or is `"`.
+    #[inline]
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        assert!(delimiter.is_ascii() && delimiter != b'"', "invalid delimiter");
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Read the next record, adding its fields into the arena.
+    /// Output is cleared, then filled with string refs of the fields.
+    /// Returns false if the reader is at EOF.
+    /// Fails if the reader fails, the record is not UTF-8, with length of
+    /// the valid prefix of the input, or the arena exceeds capacity.
+    /// Fields added before the failure are kept.
+    pub fn read_record<'a, A: BackingAlloc>(
+        &mut self,
+        arena: &'a StringArena<A>,
+        out: &mut Vec<&'a str>,
+    ) -> Result<bool> {
+        out.clear();
+        self.read_fields(|s| arena.add(s).map(|s| out.push(s)))
+    }
+
+    /// Read the next record, interning its fields, so repeated values are
+    /// stored only once.
+    /// Output is cleared, then filled with handles of the fields.
+    /// Returns false if the reader is at EOF.
+    /// Fails if the reader fails, the record is not UTF-8, with length of
+    /// the valid prefix of the input, or the arena exceeds capacity.
+    /// Fields interned before the failure are kept.
+    pub fn read_record_ids(
+        &mut self,
+        interner: &TrieInterner,
+        out: &mut Vec<StrId>,
+    ) -> Result<bool> {
+        out.clear();
+        self.read_fields(|s| interner.get_or_intern(s).map(|id| out.push(id)))
+    }
+
+    /// Read the next record, and call given function with each field.
+    fn read_fields<F: FnMut(&str) -> Result<()>>(&mut self, mut f: F) -> Result<bool> {
+        if !self.read_raw()? {
+            return Ok(false);
+        }
+        let record = core::str::from_utf8(&self.record)
+            .map_err(|e| Error::InvalidUtf8(self.offset + e.valid_up_to()))?;
+        let delimiter = self.delimiter as char;
+        let mut rest = Some(record);
+        while let Some(s) = rest {
+            let Some(quoted) = s.strip_prefix('"') else {
+                match s.split_once(delimiter) {
+                    Some((field, tail)) => {
+                        f(field)?;
+                        rest = Some(tail);
+                    }
+                    None => {
+                        f(s)?;
+                        rest = None;
+                    }
+                }
+                continue;
+            };
+            self.field.clear();
+            let (mut s, mut closed) = (quoted, false);
+            while let Some(i) = s.find('"') {
+                self.field.push_str(&s[..i]);
+                s = &s[i + 1..];
+                match s.strip_prefix('"') {
+                    Some(tail) => {
+                        self.field.push('"');
+                        s = tail;
+                    }
+                    None => {
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+            if !closed {
+                // The closing quote is missing, so the field runs to the end.
+                self.field.push_str(s);
+                s = "";
+            }
+            f(&self.field)?;
+            // Bytes between the closing quote and the delimiter are ignored.
+            rest = s.split_once(delimiter).map(|(_, tail)| tail);
+        }
+        Ok(true)
+    }
+
+    /// Read bytes of the next non-blank record, without the terminator.
+    /// Returns false if the reader is at EOF.
+    fn read_raw(&mut self) -> Result<bool> {
+        self.record.clear();
+        let mut scan = Scan::FieldStart;
+        loop {
+            let start = self.record.len();
+            if start == 0 {
+                self.offset = self.input;
+            }
+            let n = self.reader.read_until(b'\n', &mut self.record).map_err(Error::Io)?;
+            self.input += n;
+            // Only quotes starting a field are special.
+            for &b in &self.record[start..] {
+                scan = match (scan, b) {
+                    (Scan::Quoted, b'"') => Scan::QuoteInQuoted,
+                    (Scan::Quoted, _) => Scan::Quoted,
+                    (Scan::QuoteInQuoted, b'"') | (Scan::FieldStart, b'"') => Scan::Quoted,
+                    (_, b'\n') => Scan::FieldStart,
+                    (_, b) if b == self.delimiter => Scan::FieldStart,
+                    _ => Scan::Unquoted,
+                };
+            }
+            if n != 0 && scan == Scan::Quoted {
+                // A line break inside quotes.
+                continue;
+            }
+            if self.record.ends_with(b"\n") {
+                self.record.pop();
+                if self.record.ends_with(b"\r") {
+                    self.record.pop();
+                }
+            }
+            if n == 0 || !self.record.is_empty() {
+                return Ok(!self.record.is_empty());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_reader() {
+        let input = concat!(
+            "id,name,note\r\n",
+            "1,\"Smith, J\",\"say \"\"hi\"\"\"\n",
+            "\n",
+            "2,Lee,\"two\nlines\"\n",
+            "3,,",
+        );
+        let sa = StringArena::with_capacity(256);
+        let mut r = CsvReader::new(input.as_bytes());
+        let mut rows = Vec::new();
+        let mut fields = Vec::new();
+        while r.read_record(&sa, &mut fields).unwrap() {
+            rows.push(fields.clone());
+        }
+        assert_eq!(
+            rows,
+            vec![
+                vec!["id", "name", "note"],
+                vec!["1", "Smith, J", "say \"hi\""],
+                vec!["2", "Lee", "two\nlines"],
+                vec!["3", "", ""],
+            ]
+        );
+        let mut r = CsvReader::new(&b"a;\"b\";\"open"[..]).delimiter(b';');
+        assert!(r.read_record(&sa, &mut fields).unwrap());
+        assert_eq!(fields, vec!["a", "b", "open"]);
+        assert!(!r.read_record(&sa, &mut fields).unwrap());
+        let mut r = CsvReader::new(&b"\xff,a"[..]);
+        assert!(matches!(r.read_record(&sa, &mut fields), Err(Error::InvalidUtf8(0))));
+        let mut r = CsvReader::new(&b"a,b\n\nc,\xff\n"[..]);
+        assert!(r.read_record(&sa, &mut fields).unwrap());
+        assert!(matches!(r.read_record(&sa, &mut fields), Err(Error::InvalidUtf8(7))));
+    }
+
+    #[test]
+    fn test_csv_reader_stray_quotes() {
+        let sa = StringArena::with_capacity(64);
+        let mut r = CsvReader::new(&b"5\" disk,x\n\"a\"\"\nb\",\"c\"d\"\nnext,y\n"[..]);
+        let mut rows = Vec::new();
+        let mut fields = Vec::new();
+        while r.read_record(&sa, &mut fields).unwrap() {
+            rows.push(fields.clone());
+        }
+        assert_eq!(
+            rows,
+            vec![
+                vec!["5\" disk", "x"],
+                vec!["a\"\nb", "c"],
+                vec!["next", "y"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv_reader_ids() {
+        let ti = TrieInterner::with_capacity(64);
+        let mut r = CsvReader::new(&b"red,small\nblue,small\nred,large\n"[..]);
+        let mut ids = Vec::new();
+        let mut rows = Vec::new();
+        while r.read_record_ids(&ti, &mut ids).unwrap() {
+            rows.push(ids.clone());
+        }
+        assert_eq!(rows.len(), 3);
+        assert_eq!(ti.len(), 4);
+        assert_eq!(rows[0][0], rows[2][0]);
+        assert_eq!(rows[0][1], rows[1][1]);
+        assert_eq!(ti.resolve(rows[2][1]), "large");
+    }
+}
@@ -0,0 +1,98 @@
+//! Adding strings with escaping applied during the copy.
+use crate::{ArenaWriter, BackingAlloc, Result, StringArena};
+use core::fmt::{self, Write};
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Copy a string into current arena, escaping it as content of a
+    /// JSON string literal. Surrounding quotes are not added.
+    /// Returns the escaped string ref if succeeds.
+    #[inline]
+    pub fn add_json_escaped<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        add_escaped(
+            self.writer(),
+            s.as_ref(),
+            |b| b == b'"' || b == b'\\' || b < 0x20,
+            |b, w| match b {
+                b'"' => w.write_str("\\\""),
+                b'\\' => w.write_str("\\\\"),
+                b'\n' => w.write_str("\\n"),
+                b'\r' => w.write_str("\\r"),
+                b'\t' => w.write_str("\\t"),
+                0x08 => w.write_str("\\b"),
+                0x0c => w.write_str("\\f"),
+                _ => write!(w, "\\u{:04x}", b),
+            },
+        )
+    }
+
+    /// Copy a string into current arena, escaping HTML special characters.
+    /// Returns the escaped string ref if succeeds.
+    #[inline]
+    pub fn add_html_escaped<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        add_escaped(
+            self.writer(),
+            s.as_ref(),
+            |b| matches!(b, b'&' | b'<' | b'>' | b'"' | b'\''),
+            |b, w| {
+                w.write_str(match b {
+                    b'&' => "&amp;",
+                    b'<' => "&lt;",
+                    b'>' => "&gt;",
+                    b'"' => "&quot;",
+                    _ => "&#x27;",
+                })
+            },
+        )
+    }
+}
+
+/// Write the string with bytes selected by `needs` replaced by `escape`.
+/// Only ASCII bytes are selected, so the string is always split at
+/// char boundaries.
+#[inline]
+fn add_escaped<'a, A, N, E>(
+    mut w: ArenaWriter<'a, A>,
+    s: &str,
+    needs: N,
+    escape: E,
+) -> Result<&'a str>
+where
+    A: BackingAlloc,
+    N: Fn(u8) -> bool,
+    E: Fn(u8, &mut ArenaWriter<'a, A>) -> fmt::Result,
+{
+    let mut start = 0;
+    for (i, &b) in s.as_bytes().iter().enumerate() {
+        if needs(b) {
+            if w.write_str(&s[start..i]).is_err() || escape(b, &mut w).is_err() {
+                return w.finish();
+            }
+            start = i + 1;
+        }
+    }
+    let _ = w.write_str(&s[start..]);
+    w.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StringArena;
+
+    #[test]
+    fn test_add_json_escaped() {
+        let sa = StringArena::with_capacity(64);
+        let s = sa.add_json_escaped("a\"b\\c\nd\u{1}é").unwrap();
+        assert_eq!(s, "a\\\"b\\\\c\\nd\\u0001é");
+        assert_eq!(sa.add_json_escaped("plain").unwrap(), "plain");
+        let len = sa.len();
+        assert!(sa.add_json_escaped("\n".repeat(32)).is_err());
+        assert_eq!(sa.len(), len);
+    }
+
+    #[test]
+    fn test_add_html_escaped() {
+        let sa = StringArena::with_capacity(64);
+        let s = sa.add_html_escaped("<a href='x'>&\"</a>").unwrap();
+        assert_eq!(s, "&lt;a href=&#x27;x&#x27;&gt;&amp;&quot;&lt;/a&gt;");
+    }
+}
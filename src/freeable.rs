@@ -0,0 +1,236 @@
+use crate::{Error, Result, StringArena};
+use alloc::vec::Vec;
+use core::alloc::Layout;
+
+/// Size of blocks of the smallest class.
+const MIN_BLOCK: usize = 8;
+
+/// FreeId is a handle of a string stored in [`FreeableStringArena`].
+///
+/// It is invalidated once the string is freed, even if its block is
+/// reused by another string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FreeId {
+    slot: u32,
+    // Generation of the slot, bumped on free.
+    generation: u32,
+    // Generation of the arena, bumped on clear.
+    epoch: u32,
+}
+
+/// Block of the arena holding at most one string.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    offset: usize,
+    len: usize,
+    class: u32,
+    generation: u32,
+    live: bool,
+}
+
+/// FreeableStringArena is a single-thread string arena whose strings can
+/// be freed individually.
+///
+/// Each string is stored in a block of power-of-two size, carved from a
+/// [`StringArena`]. Freed blocks are kept in free lists by size class,
+/// and reused by later strings of the same class, so frequently replaced
+/// strings do not exhaust the arena. Strings are accessed by [`FreeId`]
+/// handles.
+pub struct FreeableStringArena {
+    arena: StringArena,
+    slots: Vec<Slot>,
+    // Freed slots of each size class.
+    free: Vec<Vec<u32>>,
+    len: usize,
+    strings: usize,
+}
+
+impl FreeableStringArena {
+    /// Create a new freeable string arena with given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::try_with_capacity(cap).unwrap()
+    }
+
+    /// Try to create a new freeable string arena with given capacity.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity(cap: usize) -> Result<Self> {
+        Ok(FreeableStringArena {
+            arena: StringArena::try_with_capacity(cap)?,
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            strings: 0,
+        })
+    }
+
+    /// Returns bytes of live strings.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether no string is live.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.strings == 0
+    }
+
+    /// Returns number of live strings.
+    #[inline]
+    pub fn len_strings(&self) -> usize {
+        self.strings
+    }
+
+    /// Returns bytes of all blocks carved, either live or free.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns capacity of current arena.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    /// Add a string into current arena, reusing a freed block of the same
+    /// size class if any.
+    /// Returns the handle of the string if succeeds.
+    pub fn add<T: AsRef<str>>(&mut self, s: T) -> Result<FreeId> {
+        let s = s.as_ref();
+        let class = s.len().max(MIN_BLOCK).next_power_of_two().trailing_zeros() - 3;
+        let slot = match self.free.get_mut(class as usize).and_then(Vec::pop) {
+            Some(slot) => slot,
+            None => {
+                let slot = u32::try_from(self.slots.len()).map_err(|_| Error::TooManyStrings)?;
+                let size = MIN_BLOCK << class;
+                let layout =
+                    Layout::from_size_align(size, 1).map_err(|_| Error::AllocFailed(size))?;
+                let offset = self.arena.len();
+                let ptr = self.arena.alloc_layout(layout)?;
+                // SAFETY:
+                //
+                // The block is reserved above, and zeroed so the arena
+                // only contains valid UTF-8.
+                unsafe { core::ptr::write_bytes(ptr.as_ptr(), 0, size) };
+                self.slots.push(Slot { offset, len: 0, class, generation: 0, live: false });
+                slot
+            }
+        };
+        let entry = &mut self.slots[slot as usize];
+        // SAFETY:
+        //
+        // The block is free, so no string ref of it is alive, and its size
+        // class fits the string.
+        unsafe {
+            let dst = self.arena.as_mut_ptr().add(entry.offset);
+            core::ptr::copy_nonoverlapping(s.as_ptr(), dst, s.len());
+        }
+        entry.len = s.len();
+        entry.live = true;
+        self.len += s.len();
+        self.strings += 1;
+        Ok(FreeId { slot, generation: entry.generation, epoch: self.arena.generation() })
+    }
+
+    /// Returns the string of given handle, or None if it is freed.
+    #[inline]
+    pub fn get(&self, id: FreeId) -> Option<&str> {
+        let slot = self.slots.get(id.slot as usize)?;
+        if !slot.live || slot.generation != id.generation || id.epoch != self.arena.generation() {
+            return None;
+        }
+        // SAFETY:
+        //
+        // The block is written by add() and is valid UTF-8.
+        Some(unsafe { self.arena.slice_unchecked(slot.offset, slot.offset + slot.len) })
+    }
+
+    /// Resolve the handle to string ref.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this arena, or the string
+    /// is freed.
+    #[inline]
+    pub fn resolve(&self, id: FreeId) -> &str {
+        self.get(id).expect("stale handle")
+    }
+
+    /// Free the string of given handle, so its block can be reused.
+    /// Returns whether the string is live before.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    pub fn free(&mut self, id: FreeId) -> bool {
+        if self.get(id).is_none() {
+            return false;
+        }
+        let slot = &mut self.slots[id.slot as usize];
+        // SAFETY:
+        //
+        // The block is within the arena and no string ref of it is alive.
+        unsafe { core::ptr::write_bytes(self.arena.as_mut_ptr().add(slot.offset), 0, slot.len) };
+        self.len -= slot.len;
+        self.strings -= 1;
+        slot.live = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        let class = slot.class as usize;
+        if self.free.len() <= class {
+            self.free.resize_with(class + 1, Vec::new);
+        }
+        self.free[class].push(id.slot);
+        true
+    }
+
+    /// Clear the arena so its memory can be reused.
+    /// Handles returned before are invalidated.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.arena.clear();
+        self.slots.clear();
+        self.free.clear();
+        self.len = 0;
+        self.strings = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freeable_string_arena() {
+        let mut fa = FreeableStringArena::with_capacity(64);
+        let a = fa.add("hello").unwrap();
+        let b = fa.add("a string of 24 bytes ...").unwrap();
+        assert_eq!(fa.used(), 8 + 32);
+        assert_eq!(fa.len(), 29);
+        assert_eq!(fa.resolve(a), "hello");
+        assert!(fa.free(a));
+        assert!(!fa.free(a));
+        assert_eq!(fa.get(a), None);
+        let c = fa.add("world!").unwrap();
+        assert_ne!(a, c);
+        assert_eq!(fa.used(), 40);
+        assert_eq!(fa.resolve(c), "world!");
+        assert_eq!(fa.get(a), None);
+        assert_eq!(fa.resolve(b), "a string of 24 bytes ...");
+        let d = fa.add("").unwrap();
+        assert_eq!(fa.used(), 48);
+        assert!(fa.add("x".repeat(17)).is_err());
+        assert!(fa.free(b));
+        let e = fa.add("x".repeat(17)).unwrap();
+        assert_eq!((e.slot, fa.used()), (b.slot, 48));
+        assert_eq!(fa.len_strings(), 3);
+        fa.clear();
+        assert!(fa.is_empty());
+        fa.add("").unwrap();
+        assert_eq!(fa.get(a), None);
+        assert_eq!(fa.get(d), None);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{IndexedStringArena, StrId};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// FrontCodedStringArena is a read-only dictionary of distinct strings in
+/// lexicographical order, compressed by front coding.
+///
+/// Strings are grouped into buckets. The first string of each bucket is
+/// stored in full, and each following one as the length of prefix shared
+/// with its predecessor and the remaining suffix, so sorted strings with
+/// long common prefixes, e.g. paths and URLs, take much less space.
+///
+/// Strings are decoded into a scratch buffer provided by the caller. The
+/// handle of a string is its rank, as in
+/// [`SortedStringArena`](crate::SortedStringArena).
+pub struct FrontCodedStringArena {
+    // Buckets of varint encoded lengths and string bytes.
+    data: Vec<u8>,
+    // Start of each bucket in data.
+    buckets: Vec<usize>,
+    bucket_size: usize,
+    strings: usize,
+}
+
+impl FrontCodedStringArena {
+    /// Returns bytes of the encoded dictionary.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns whether the dictionary is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.strings == 0
+    }
+
+    /// Returns number of distinct strings stored.
+    #[inline]
+    pub fn len_strings(&self) -> usize {
+        self.strings
+    }
+
+    /// Returns number of strings in each bucket.
+    #[inline]
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// Returns the handle of the string if exists.
+    pub fn lookup<T: AsRef<str>>(&self, s: T) -> Option<StrId> {
+        let s = s.as_ref();
+        // Find the last bucket whose first string is not greater.
+        let (mut lo, mut hi) = (0, self.buckets.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let mut pos = self.buckets[mid];
+            let len = read_varint(&self.data, &mut pos);
+            if &self.data[pos..pos + len] <= s.as_bytes() {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let b = lo.checked_sub(1)?;
+        let mut buf = String::new();
+        let mut pos = self.buckets[b];
+        let end = (b * self.bucket_size + self.bucket_size).min(self.strings);
+        for i in b * self.bucket_size..end {
+            self.decode_next(&mut pos, &mut buf, i % self.bucket_size == 0);
+            match buf.as_str().cmp(s) {
+                Ordering::Less => (),
+                Ordering::Equal => return Some(StrId::new(i as u32, 0)),
+                Ordering::Greater => break,
+            }
+        }
+        None
+    }
+
+    /// Resolve the handle to string, decoded into given buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this dictionary.
+    #[inline]
+    pub fn resolve<'a>(&self, id: StrId, buf: &'a mut String) -> &'a str {
+        assert!(id.generation() == 0 && id.index() < self.strings, "stale handle");
+        self.decode(id.index(), buf);
+        buf
+    }
+
+    /// Returns the i-th string in sorted order if exists, decoded into
+    /// given buffer.
+    #[inline]
+    pub fn get<'a>(&self, i: usize, buf: &'a mut String) -> Option<&'a str> {
+        if i >= self.strings {
+            return None;
+        }
+        self.decode(i, buf);
+        Some(buf)
+    }
+
+    /// Call given function with all stored strings in sorted order.
+    /// Each bucket is decoded only once.
+    pub fn for_each<F: FnMut(&str)>(&self, mut f: F) {
+        let mut buf = String::new();
+        let mut pos = 0;
+        for i in 0..self.strings {
+            self.decode_next(&mut pos, &mut buf, i % self.bucket_size == 0);
+            f(&buf);
+        }
+    }
+
+    #[inline]
+    fn decode(&self, i: usize, buf: &mut String) {
+        let mut pos = self.buckets[i / self.bucket_size];
+        for j in 0..=i % self.bucket_size {
+            self.decode_next(&mut pos, buf, j == 0);
+        }
+    }
+
+    /// Decode the string at given position, which shares prefix with the
+    /// string in buffer unless it is the first of a bucket.
+    #[inline]
+    fn decode_next(&self, pos: &mut usize, buf: &mut String, first: bool) {
+        let shared = if first { 0 } else { read_varint(&self.data, pos) };
+        let len = read_varint(&self.data, pos);
+        let suffix = &self.data[*pos..*pos + len];
+        *pos += len;
+        buf.truncate(shared);
+        // SAFETY:
+        //
+        // Shared prefixes end at char boundaries, so each suffix is a
+        // valid string copied from the source arena.
+        buf.push_str(unsafe { core::str::from_utf8_unchecked(suffix) });
+    }
+}
+
+impl IndexedStringArena {
+    /// Build a front coded dictionary of distinct strings stored in the
+    /// arena, with given number of strings in each bucket. Larger buckets
+    /// save more space but take longer to decode a string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bucket size is zero.
+    pub fn into_front_coded(self, bucket_size: usize) -> FrontCodedStringArena {
+        assert!(bucket_size != 0, "zero bucket size");
+        let mut strs: Vec<&str> = self.iter().collect();
+        strs.sort_unstable();
+        strs.dedup();
+        let mut data = Vec::new();
+        let mut buckets = Vec::with_capacity(strs.len().div_ceil(bucket_size));
+        let mut prev = "";
+        for (i, &s) in strs.iter().enumerate() {
+            let suffix = if i % bucket_size == 0 {
+                buckets.push(data.len());
+                s
+            } else {
+                let mut shared = prev.bytes().zip(s.bytes()).take_while(|(a, b)| a == b).count();
+                while !s.is_char_boundary(shared) {
+                    shared -= 1;
+                }
+                write_varint(&mut data, shared);
+                &s[shared..]
+            };
+            write_varint(&mut data, suffix.len());
+            data.extend_from_slice(suffix.as_bytes());
+            prev = s;
+        }
+        FrontCodedStringArena { data, buckets, bucket_size, strings: strs.len() }
+    }
+}
+
+#[inline]
+fn write_varint(data: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        data.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    data.push(n as u8);
+}
+
+#[inline]
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let (mut n, mut shift) = (0, 0);
+    loop {
+        let b = data[*pos];
+        *pos += 1;
+        n |= ((b & 0x7f) as usize) << shift;
+        if b < 0x80 {
+            return n;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_coded_string_arena() {
+        let ia = IndexedStringArena::with_capacity(256);
+        let strs = [
+            "/usr/lib/libc.so",
+            "/usr/lib/libm.so",
+            "/usr/bin/ls",
+            "/usr/lib/libc.so",
+            "/usr/bin/cat",
+            "",
+            "/usr/lib/中文",
+            "/usr/lib/丰",
+            "/usr/lib/libz.so",
+        ];
+        for s in strs {
+            ia.add(s).unwrap();
+        }
+        let total = ia.len();
+        let fa = ia.into_front_coded(3);
+        assert_eq!(fa.len_strings(), 8);
+        assert!(fa.len() < total);
+        let mut out = Vec::new();
+        fa.for_each(|s| out.push(s.to_string()));
+        let mut sorted: Vec<&str> = strs.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(out, sorted);
+        let mut buf = String::new();
+        for (i, s) in sorted.iter().enumerate() {
+            let id = fa.lookup(s).unwrap();
+            assert_eq!(id.index(), i);
+            assert_eq!(fa.resolve(id, &mut buf), *s);
+        }
+        // Prefix shared within the bucket is cut at char boundary.
+        assert_eq!(fa.get(7, &mut buf), Some("/usr/lib/丰"));
+        assert_eq!(fa.get(8, &mut buf), None);
+        assert_eq!(fa.lookup("/usr/lib"), None);
+        assert_eq!(fa.lookup("/usr/lib/中"), None);
+        assert_eq!(fa.lookup("zzz"), None);
+        let empty = IndexedStringArena::with_capacity(0).into_front_coded(4);
+        assert!(empty.is_empty());
+        assert_eq!(empty.lookup(""), None);
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut data = Vec::new();
+        for n in [0, 127, 128, 300, usize::MAX] {
+            write_varint(&mut data, n);
+        }
+        let mut pos = 0;
+        for n in [0, 127, 128, 300, usize::MAX] {
+            assert_eq!(read_varint(&data, &mut pos), n);
+        }
+        assert_eq!(pos, data.len());
+    }
+}
@@ -0,0 +1,415 @@
+use crate::{IndexedStringArena, StrId, StringArena};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+use core::ptr::NonNull;
+
+#[cfg(all(feature = "std", feature = "mmap", unix))]
+use crate::persist::Mapping;
+
+// The buffer is kept as raw pointer, so the snapshot has no interior
+// mutability and ArenaStr can be used as map key.
+struct Frozen {
+    ptr: NonNull<u8>,
+    len: usize,
+    generation: u32,
+    storage: Storage,
+}
+
+enum Storage {
+    // Raw parts of the arena, with offsets of strings.
+    Heap {
+        cap: usize,
+        align: usize,
+        offsets: Vec<usize>,
+    },
+    // Read-only file mapping, with offsets stored inside it.
+    #[cfg(all(feature = "std", feature = "mmap", unix))]
+    Mapped {
+        _map: Mapping,
+        // Offsets decoded from the file, if they cannot be used in place.
+        _decoded: Vec<usize>,
+        offsets: NonNull<usize>,
+        strings: usize,
+    },
+}
+
+// SAFETY:
+//
+// The buffer is never modified once frozen.
+unsafe impl Send for Frozen {}
+unsafe impl Sync for Frozen {}
+
+impl Frozen {
+    /// Returns the string ref of given region.
+    ///
+    /// # Safety
+    ///
+    /// The region must be within a string written before freezing.
+    #[inline]
+    unsafe fn slice_unchecked(&self, start: usize, end: usize) -> &str {
+        let bs = core::slice::from_raw_parts(self.ptr.as_ptr().add(start), end - start);
+        core::str::from_utf8_unchecked(bs)
+    }
+
+    /// Returns offsets of all strings, with the end of the last string.
+    #[inline]
+    fn offsets(&self) -> &[usize] {
+        match &self.storage {
+            Storage::Heap { offsets, .. } => offsets,
+            #[cfg(all(feature = "std", feature = "mmap", unix))]
+            // SAFETY:
+            //
+            // The offsets are validated and kept alive with the mapping.
+            Storage::Mapped {
+                offsets, strings, ..
+            } => unsafe { core::slice::from_raw_parts(offsets.as_ptr(), strings + 1) },
+        }
+    }
+}
+
+impl Drop for Frozen {
+    #[inline]
+    fn drop(&mut self) {
+        match self.storage {
+            // SAFETY:
+            //
+            // The raw parts are taken from an arena in freeze().
+            Storage::Heap { cap, align, .. } => {
+                drop(unsafe { StringArena::from_raw_parts(self.ptr, self.len, cap, align) })
+            }
+            // The mapping is unmapped by its own drop.
+            #[cfg(all(feature = "std", feature = "mmap", unix))]
+            Storage::Mapped { .. } => (),
+        }
+    }
+}
+
+/// FrozenArena is an immutable snapshot of [`IndexedStringArena`].
+///
+/// It can be shared among threads and cloned cheaply. Strings can be
+/// resolved as [`ArenaStr`] handles which keep the snapshot alive.
+#[derive(Clone)]
+pub struct FrozenArena {
+    inner: Arc<Frozen>,
+}
+
+impl FrozenArena {
+    /// Returns bytes of all strings.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len
+    }
+
+    /// Returns whether the snapshot is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the generation of the arena when frozen, which handles
+    /// must be taken in.
+    #[inline]
+    pub fn generation(&self) -> u32 {
+        self.inner.generation
+    }
+
+    /// Resolve the handle to string ref.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is stale before freezing. A handle returned by
+    /// another arena may resolve to an unrelated string or panic.
+    #[inline]
+    pub fn resolve(&self, id: StrId) -> &str {
+        let (start, end) = self.bounds(id);
+        // SAFETY:
+        //
+        // The region is a string written before freezing.
+        unsafe { self.inner.slice_unchecked(start, end) }
+    }
+
+    /// Returns owned handle of the string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is stale before freezing. A handle returned by
+    /// another arena may resolve to an unrelated string or panic.
+    #[inline]
+    pub fn get(&self, id: StrId) -> ArenaStr {
+        let (start, end) = self.bounds(id);
+        ArenaStr {
+            arena: self.clone(),
+            start,
+            end,
+        }
+    }
+
+    /// Returns an iterator over all strings in insertion order.
+    #[inline]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &str> + '_ {
+        self.inner
+            .offsets()
+            .windows(2)
+            // SAFETY:
+            //
+            // Each region is a string written before freezing.
+            .map(|w| unsafe { self.inner.slice_unchecked(w[0], w[1]) })
+    }
+
+    #[inline]
+    fn bounds(&self, id: StrId) -> (usize, usize) {
+        assert!(id.is_of(self.inner.generation), "stale handle");
+        let offsets = self.inner.offsets();
+        (offsets[id.index()], offsets[id.index() + 1])
+    }
+
+    /// Create a snapshot over the arena, with handles taken in given
+    /// generation.
+    ///
+    /// # Safety
+    ///
+    /// Offsets must be ascending char boundaries of bytes in the arena,
+    /// starting at 0 and ending at its length.
+    #[inline]
+    pub(crate) unsafe fn from_heap(
+        arena: StringArena,
+        offsets: Vec<usize>,
+        generation: u32,
+    ) -> Self {
+        let (ptr, len, cap, align) = arena.into_raw_parts();
+        FrozenArena {
+            inner: Arc::new(Frozen {
+                ptr,
+                len,
+                generation,
+                storage: Storage::Heap {
+                    cap,
+                    align,
+                    offsets,
+                },
+            }),
+        }
+    }
+
+    /// Create a snapshot over a validated file mapping.
+    ///
+    /// # Safety
+    ///
+    /// Offsets must be ascending char boundaries of the bytes, which are
+    /// valid UTF-8. Offsets are within the mapping or the decoded list,
+    /// and bytes are within the mapping.
+    #[cfg(all(feature = "std", feature = "mmap", unix))]
+    #[inline]
+    pub(crate) unsafe fn from_mapping(
+        map: Mapping,
+        decoded: Vec<usize>,
+        offsets: NonNull<usize>,
+        strings: usize,
+        ptr: NonNull<u8>,
+        generation: u32,
+    ) -> Self {
+        let len = *offsets.as_ptr().add(strings);
+        FrozenArena {
+            inner: Arc::new(Frozen {
+                ptr,
+                len,
+                generation,
+                storage: Storage::Mapped {
+                    _map: map,
+                    _decoded: decoded,
+                    offsets,
+                    strings,
+                },
+            }),
+        }
+    }
+}
+
+impl IndexedStringArena {
+    /// Freeze the arena into an immutable snapshot.
+    #[inline]
+    pub fn freeze(self) -> FrozenArena {
+        let (arena, offsets) = self.into_parts();
+        let generation = arena.generation();
+        // SAFETY:
+        //
+        // Offsets are recorded by the indexed arena on each add.
+        unsafe { FrozenArena::from_heap(arena, offsets, generation) }
+    }
+}
+
+/// ArenaStr is an owned handle of a string in [`FrozenArena`].
+///
+/// It dereferences to `str`, and keeps the snapshot alive. It compares
+/// and hashes as `str`, so maps keyed by it can be looked up by `&str`.
+#[derive(Clone)]
+pub struct ArenaStr {
+    arena: FrozenArena,
+    start: usize,
+    end: usize,
+}
+
+impl ArenaStr {
+    /// Returns the string ref.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        // SAFETY:
+        //
+        // The region is a string written before freezing.
+        unsafe { self.arena.inner.slice_unchecked(self.start, self.end) }
+    }
+
+    /// Returns the snapshot which the string belongs to.
+    #[inline]
+    pub fn arena(&self) -> &FrozenArena {
+        &self.arena
+    }
+}
+
+impl Deref for ArenaStr {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ArenaStr {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// Owners of bytes, e.g. `bytes::Bytes::from_owner`, can keep the snapshot
+// alive and view the string without copying.
+impl AsRef<[u8]> for ArenaStr {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_str().as_bytes()
+    }
+}
+
+impl Borrow<str> for ArenaStr {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for ArenaStr {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ArenaStr {}
+
+impl PartialEq<str> for ArenaStr {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ArenaStr {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for ArenaStr {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ArenaStr {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for ArenaStr {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for ArenaStr {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ArenaStr {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::IndexedStringArena;
+
+    #[test]
+    fn test_frozen_arena() {
+        let ia = IndexedStringArena::with_capacity(16);
+        let id1 = ia.add_id("hello").unwrap();
+        let id2 = ia.add_id("world").unwrap();
+        let fa = ia.freeze();
+        let s1 = fa.get(id1);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let fa = fa.clone();
+                std::thread::spawn(move || fa.get(id2))
+            })
+            .collect();
+        drop(fa);
+        for h in handles {
+            let s2 = h.join().unwrap();
+            assert_eq!(&*s2, "world");
+        }
+        assert_eq!(s1.as_str(), "hello");
+        assert_eq!(s1.arena().iter().collect::<Vec<_>>(), vec!["hello", "world"]);
+        assert_eq!(s1.arena().resolve(id2), "world");
+        assert_eq!(format!("{:?}", s1), "\"hello\"");
+        let bs: &[u8] = s1.as_ref();
+        assert_eq!(bs.as_ptr(), s1.as_str().as_ptr());
+    }
+
+    #[test]
+    fn test_arena_str_as_key() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let ia = IndexedStringArena::with_capacity(16);
+        let id1 = ia.add_id("world").unwrap();
+        let id2 = ia.add_id("hello").unwrap();
+        let id3 = ia.add_id("hello").unwrap();
+        let fa = ia.freeze();
+        assert_eq!(fa.get(id2), fa.get(id3));
+        assert!(fa.get(id2) < fa.get(id1));
+        assert_eq!(fa.get(id1), "world");
+        let mut hm = HashMap::new();
+        hm.insert(fa.get(id1), 1);
+        hm.insert(fa.get(id2), 2);
+        *hm.get_mut("hello").unwrap() += 1;
+        assert_eq!(hm.get("hello"), Some(&3));
+        let bm: BTreeMap<_, _> = hm.into_iter().collect();
+        assert_eq!(bm.get("world"), Some(&1));
+        assert_eq!(bm.keys().next().unwrap(), "hello");
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{Error, StringArena, StringArenaBuilder};
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+
+/// GrowthPolicy controls sizes of chunks allocated by [`GrowableStringArena`].
+#[derive(Debug, Clone, Copy, Default)]
+pub enum GrowthPolicy {
+    /// Every chunk has the initial chunk size.
+    #[default]
+    Fixed,
+    /// Each chunk is twice the size of the previous one.
+    Doubling,
+    /// Each chunk is larger than the previous one by given bytes.
+    Increment(usize),
+    /// Each chunk is the sum of sizes of the previous two.
+    Fibonacci,
+    /// Size of each chunk is computed from size of the previous one and
+    /// length of the string to add.
+    Custom(fn(usize, usize) -> usize),
+}
+
+/// GrowableStringArena is a single-thread append-only string arena
+/// which allocates additional chunks on demand.
+///
+/// Each chunk is a [`StringArena`], sized by the [`GrowthPolicy`]. Chunks
+/// are never moved or reallocated once created, so all string refs stay
+/// valid until the arena is dropped or renewed.
+pub struct GrowableStringArena {
+    chunks: UnsafeCell<Vec<StringArena>>,
+    chunk_size: usize,
+    growth: GrowthPolicy,
+    max_chunk_size: usize,
+    // Settings of new chunks, except the capacity.
+    template: StringArenaBuilder,
+}
+
+impl GrowableStringArena {
+    /// Create a new growable string arena with given chunk size.
+    /// The first chunk is allocated lazily on first add.
+    #[inline]
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        Self::with_template(chunk_size, StringArena::builder())
+    }
+
+    /// Create a new growable string arena whose chunks are created by
+    /// given builder, with capacity overridden.
+    #[inline]
+    pub(crate) fn with_template(chunk_size: usize, template: StringArenaBuilder) -> Self {
+        GrowableStringArena {
+            chunks: UnsafeCell::new(Vec::new()),
+            chunk_size,
+            growth: GrowthPolicy::Fixed,
+            max_chunk_size: usize::MAX,
+            template,
+        }
+    }
+
+    /// Set the growth policy of chunk sizes.
+    #[inline]
+    pub fn growth(mut self, growth: GrowthPolicy) -> Self {
+        self.growth = growth;
+        self
+    }
+
+    /// Set the maximum size of chunks allocated by the growth policy.
+    /// Strings longer than it still get a dedicated chunk.
+    #[inline]
+    pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.max_chunk_size = max_chunk_size;
+        self
+    }
+
+    /// Returns the size of the first chunk.
+    #[inline]
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns number of chunks allocated.
+    #[inline]
+    pub fn chunks(&self) -> usize {
+        self.chunks_ref().len()
+    }
+
+    /// Returns bytes written of all chunks.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.chunks_ref().iter().map(|c| c.len()).sum()
+    }
+
+    /// Returns whether the arena is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.chunks_ref().iter().all(|c| c.is_empty())
+    }
+
+    /// Returns total capacity of all chunks.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.chunks_ref().iter().map(|c| c.capacity()).sum()
+    }
+
+    /// Add a string into current arena.
+    /// Returns the string ref.
+    /// If current chunk does not have enough space, a new chunk is
+    /// allocated. Strings longer than chunk size get a dedicated chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails, or the memory budget chunks are
+    /// registered with is exceeded.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> &str {
+        let s = s.as_ref();
+        // SAFETY:
+        //
+        // The chunk list is guaranteed not to be accessed concurrently,
+        // and no reference to the list itself escapes this method.
+        let chunks = unsafe { &mut *self.chunks.get() };
+        let res = match chunks.last().map(|c| c.add(s)) {
+            Some(Ok(res)) => res,
+            None | Some(Err(Error::ExceedsCapacity(_))) => {
+                let cap = self.next_chunk_size(chunks, s.len());
+                chunks.push(self.template.clone().capacity(cap).build());
+                chunks.last().unwrap().add(s).unwrap()
+            }
+            Some(Err(e)) => panic!("failed to add string: {}", e),
+        };
+        // SAFETY:
+        //
+        // The string is stored in the heap buffer of a chunk, which is
+        // never moved or freed while self is alive, even if the chunk list
+        // itself reallocates.
+        unsafe { &*(res as *const str) }
+    }
+
+    /// Clear the arena so its memory can be reused.
+    /// Only the most recently allocated chunk is retained.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        let chunks = self.chunks.get_mut();
+        if let Some(mut last) = chunks.pop() {
+            last.clear();
+            chunks.clear();
+            chunks.push(last);
+        }
+    }
+
+    /// Renew the arena for future usage.
+    /// Only the most recently allocated chunk is retained.
+    /// This is safe because all string refs associated to this arena
+    /// must be dropped before this method call.
+    #[inline]
+    pub fn renew(mut self) -> Self {
+        self.clear();
+        self
+    }
+
+    /// Returns size of the chunk to allocate for a string of given length.
+    #[inline]
+    fn next_chunk_size(&self, chunks: &[StringArena], needed: usize) -> usize {
+        let size = match chunks {
+            [] => self.chunk_size,
+            [.., last] => {
+                let curr = last.capacity();
+                match self.growth {
+                    GrowthPolicy::Fixed => self.chunk_size,
+                    GrowthPolicy::Doubling => curr.saturating_mul(2),
+                    GrowthPolicy::Increment(n) => curr.saturating_add(n),
+                    GrowthPolicy::Fibonacci => {
+                        let prev = chunks.len().checked_sub(2).map_or(0, |i| chunks[i].capacity());
+                        curr.saturating_add(prev)
+                    }
+                    GrowthPolicy::Custom(f) => f(curr, needed),
+                }
+            }
+        };
+        size.min(self.max_chunk_size).max(needed)
+    }
+
+    #[inline]
+    fn chunks_ref(&self) -> &[StringArena] {
+        // SAFETY:
+        //
+        // The chunk list is only mutated inside add(), which never
+        // runs concurrently with this shared access.
+        unsafe { &*self.chunks.get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_growable_string_arena() {
+        let ga = GrowableStringArena::with_chunk_size(8);
+        assert!(ga.is_empty());
+        assert_eq!(ga.chunks(), 0);
+        let s1 = ga.add("hello");
+        let s2 = ga.add("world");
+        assert_eq!(ga.chunks(), 2);
+        let s3 = ga.add("a very long string");
+        assert_eq!(ga.chunks(), 3);
+        assert_eq!(s1, "hello");
+        assert_eq!(s2, "world");
+        assert_eq!(s3, "a very long string");
+        assert_eq!(ga.len(), 28);
+        assert_eq!(ga.capacity(), 34);
+        let ga2 = ga.renew();
+        assert!(ga2.is_empty());
+        assert_eq!(ga2.chunks(), 1);
+    }
+
+    #[test]
+    fn test_growth_policy() {
+        fn sizes(ga: &GrowableStringArena) -> Vec<usize> {
+            ga.chunks_ref().iter().map(|c| c.capacity()).collect()
+        }
+        let fill = |ga: &GrowableStringArena| {
+            for _ in 0..12 {
+                ga.add("abcd");
+            }
+        };
+        let ga = GrowableStringArena::with_chunk_size(4).growth(GrowthPolicy::Doubling);
+        fill(&ga);
+        assert_eq!(sizes(&ga), vec![4, 8, 16, 32]);
+        let ga = GrowableStringArena::with_chunk_size(4)
+            .growth(GrowthPolicy::Doubling)
+            .max_chunk_size(8);
+        fill(&ga);
+        assert_eq!(sizes(&ga), vec![4, 8, 8, 8, 8, 8, 8]);
+        let ga = GrowableStringArena::with_chunk_size(4).growth(GrowthPolicy::Increment(4));
+        fill(&ga);
+        assert_eq!(sizes(&ga), vec![4, 8, 12, 16, 20]);
+        let ga = GrowableStringArena::with_chunk_size(4).growth(GrowthPolicy::Fibonacci);
+        fill(&ga);
+        assert_eq!(sizes(&ga), vec![4, 4, 8, 12, 20]);
+        let ga = GrowableStringArena::with_chunk_size(4)
+            .growth(GrowthPolicy::Custom(|curr, needed| curr + needed * 2));
+        fill(&ga);
+        assert_eq!(sizes(&ga), vec![4, 12, 20, 28]);
+    }
+}
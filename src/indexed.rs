@@ -0,0 +1,628 @@
+use crate::{Error, Result, Span, StringArena};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+/// Bits of the generation kept in a handle.
+const GENERATION_BITS: u32 = 4;
+
+/// Mask of the generation kept in a handle.
+const GENERATION_MASK: u32 = (1 << GENERATION_BITS) - 1;
+
+/// Maximum position of a string which a handle can refer to.
+pub(crate) const MAX_INDEX: u32 = u32::MAX >> GENERATION_BITS;
+
+/// StrId is a compact handle of a string stored in an indexed arena.
+///
+/// It packs the position of the string in insertion order with the low
+/// 4 bits of the generation of the arena in a `u32`, and can be resolved
+/// back to string ref by the arena which returned it, before the arena is
+/// cleared. A handle stale for a multiple of 16 generations is not
+/// detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StrId(u32);
+
+impl StrId {
+    #[inline]
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        debug_assert!(index <= MAX_INDEX);
+        StrId(index | generation << (32 - GENERATION_BITS))
+    }
+
+    /// Returns the position of the string in insertion order.
+    #[inline]
+    pub fn index(self) -> usize {
+        (self.0 & MAX_INDEX) as usize
+    }
+
+    /// Returns the low 4 bits of the generation of arena the handle is
+    /// taken in.
+    #[inline]
+    pub fn generation(self) -> u32 {
+        self.0 >> (32 - GENERATION_BITS)
+    }
+
+    /// Returns whether the handle is taken in given generation of arena.
+    #[inline]
+    pub(crate) fn is_of(self, generation: u32) -> bool {
+        self.generation() == generation & GENERATION_MASK
+    }
+
+    /// Returns the handle encoded as an integer, e.g. to send it to
+    /// another process sharing the arena.
+    #[inline]
+    pub fn to_bits(self) -> u32 {
+        self.0
+    }
+
+    /// Create the handle from an integer returned by
+    /// [`to_bits`](Self::to_bits). Only staleness of the handle is checked
+    /// when resolved, so bits not returned by the same arena may resolve to
+    /// an unrelated string or panic.
+    #[inline]
+    pub fn from_bits(bits: u32) -> Self {
+        StrId(bits)
+    }
+}
+
+/// IndexedStringArena is a single-thread append-only string arena
+/// which records boundary of each string.
+///
+/// Besides string refs, it can return compact [`StrId`] handles which do
+/// not borrow the arena, so they can be stored alongside the arena itself.
+pub struct IndexedStringArena {
+    arena: StringArena,
+    // Offsets of all strings with a leading zero, so the i-th string
+    // spans offsets[i]..offsets[i+1].
+    offsets: UnsafeCell<Vec<usize>>,
+}
+
+impl IndexedStringArena {
+    /// Create a new indexed string arena with given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::try_with_capacity(cap).unwrap()
+    }
+
+    /// Try to create a new indexed string arena with given capacity.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity(cap: usize) -> Result<Self> {
+        Ok(IndexedStringArena {
+            arena: StringArena::try_with_capacity(cap)?,
+            offsets: UnsafeCell::new(vec![0]),
+        })
+    }
+
+    /// Returns bytes written of current arena.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns whether the arena is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    /// Returns capacity of current arena.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    /// Add a string into current arena.
+    /// Returns the string ref if succeeds.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        self.add_id(s).map(|id| self.resolve(id))
+    }
+
+    /// Add a string into current arena.
+    /// Returns the handle of the string if succeeds.
+    #[inline]
+    pub fn add_id<T: AsRef<str>>(&self, s: T) -> Result<StrId> {
+        let id = u32::try_from(self.len_strings())
+            .ok()
+            .filter(|&id| id <= MAX_INDEX)
+            .ok_or(Error::TooManyStrings)?;
+        self.arena.add(s)?;
+        // SAFETY:
+        //
+        // The offset list is guaranteed not to be accessed concurrently,
+        // and no reference to the list is alive here.
+        unsafe { (*self.offsets.get()).push(self.arena.len()) };
+        Ok(StrId::new(id, self.arena.generation()))
+    }
+
+    /// Resolve the handle to string ref.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena is cleared after the handle is returned. A
+    /// handle returned by another arena may resolve to an unrelated string
+    /// or panic.
+    #[inline]
+    pub fn resolve(&self, id: StrId) -> &str {
+        let (start, end) = self.bounds(id);
+        // SAFETY:
+        //
+        // The region is written by add_id() and is valid UTF-8.
+        unsafe { self.arena.slice_unchecked(start, end) }
+    }
+
+    /// Returns number of strings stored.
+    #[inline]
+    pub fn len_strings(&self) -> usize {
+        self.offsets_ref().len() - 1
+    }
+
+    /// Returns the i-th string in insertion order if exists.
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<&str> {
+        if i < self.len_strings() {
+            Some(self.resolve(self.id(i)))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the span of the string of given handle, which is relative
+    /// to [`values`](Self::values).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena is cleared after the handle is returned. A
+    /// handle returned by another arena may yield an unrelated span or
+    /// panic.
+    #[inline]
+    pub fn span(&self, id: StrId) -> Span {
+        let (start, end) = self.bounds(id);
+        Span::tagged(start, end - start, self.arena.generation())
+    }
+
+    /// Resolve the span to string ref.
+    /// Returns None if the span is stale, out of stored bytes, or the
+    /// bytes are not valid UTF-8, e.g. it does not start or end at char
+    /// boundaries.
+    #[inline]
+    pub fn resolve_span(&self, span: Span) -> Option<&str> {
+        if span.generation() != self.arena.generation() {
+            return None;
+        }
+        let bs = self.values().get(span.offset()..span.end())?;
+        core::str::from_utf8(bs).ok()
+    }
+
+    /// Returns the generation of the arena, which is bumped on clear.
+    #[inline]
+    pub fn generation(&self) -> u32 {
+        self.arena.generation()
+    }
+
+    /// Returns bytes of all stored strings.
+    #[inline]
+    pub fn values(&self) -> &[u8] {
+        // SAFETY:
+        //
+        // Written region is immutable.
+        unsafe { self.arena.slice_unchecked(0, self.len()).as_bytes() }
+    }
+
+    /// Returns an iterator over all stored strings in insertion order.
+    #[inline]
+    pub fn iter(&self) -> IndexedIter<'_> {
+        IndexedIter {
+            arena: self,
+            idx: 0,
+            end: self.len_strings(),
+        }
+    }
+
+    /// Copy all strings of another arena into current arena.
+    /// Returns the function mapping handles of the other arena to
+    /// handles of current arena if succeeds. Fails if they exceed remained
+    /// capacity, and nothing is kept in the arena.
+    #[inline]
+    pub fn append_arena(
+        &mut self,
+        other: &IndexedStringArena,
+    ) -> Result<impl Fn(StrId) -> StrId + Copy> {
+        let n = self.len_strings();
+        let other_offsets = other.offsets_ref();
+        // Id of the last string must fit in u32.
+        if let Some(last) = (n + other.len_strings()).checked_sub(1) {
+            if last > MAX_INDEX as usize {
+                return Err(Error::TooManyStrings);
+            }
+        }
+        let base = self.len();
+        self.arena.append_arena(&other.arena)?;
+        self.offsets
+            .get_mut()
+            .extend(other_offsets[1..].iter().map(|o| o + base));
+        let (n, generation) = (n as u32, self.arena.generation());
+        Ok(move |id: StrId| StrId::new(n + id.index() as u32, generation))
+    }
+
+    /// Move strings of given handles to the front in insertion order, and
+    /// discard all others, so their space can be reused.
+    /// Returns the table mapping live handles to new ones, as all handles
+    /// returned before are invalidated.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any handle is stale. A handle returned by another arena
+    /// may keep an unrelated string or panic.
+    pub fn compact<I: IntoIterator<Item = StrId>>(&mut self, live: I) -> RemapTable {
+        let mut live: Vec<usize> = live
+            .into_iter()
+            .map(|id| {
+                self.bounds(id);
+                id.index()
+            })
+            .collect();
+        live.sort_unstable();
+        live.dedup();
+        let mut map = vec![NONE; self.len_strings()];
+        let from = self.arena.generation();
+        let offsets = self.offsets.get_mut();
+        let mut compacted = Vec::with_capacity(live.len() + 1);
+        compacted.push(0);
+        let ptr = self.arena.as_mut_ptr();
+        for (new, &i) in live.iter().enumerate() {
+            let (start, end, dst) = (offsets[i], offsets[i + 1], compacted[new]);
+            // SAFETY:
+            //
+            // Both regions are within written bytes, and the destination
+            // never exceeds the source as strings are moved in order.
+            unsafe { core::ptr::copy(ptr.add(start), ptr.add(dst), end - start) };
+            compacted.push(dst + end - start);
+            map[i] = new as u32;
+        }
+        self.arena.discard(compacted[live.len()]);
+        *offsets = compacted;
+        RemapTable { map, from, to: self.arena.generation() }
+    }
+
+    /// Clear the arena so its memory can be reused.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.arena.clear();
+        self.offsets.get_mut().truncate(1);
+    }
+
+    /// Renew the arena for future usage.
+    /// This is safe because all string refs associated to this arena
+    /// must be dropped before this method call.
+    #[inline]
+    pub fn renew(mut self) -> Self {
+        self.clear();
+        self
+    }
+
+    #[inline]
+    pub(crate) fn into_parts(self) -> (StringArena, Vec<usize>) {
+        (self.arena, self.offsets.into_inner())
+    }
+
+    #[inline]
+    pub(crate) fn offsets_mut(&mut self) -> &[usize] {
+        self.offsets.get_mut()
+    }
+
+    /// Returns the handle of the i-th string in current generation.
+    #[inline]
+    pub(crate) fn id(&self, i: usize) -> StrId {
+        StrId::new(i as u32, self.arena.generation())
+    }
+
+    #[inline]
+    fn bounds(&self, id: StrId) -> (usize, usize) {
+        assert!(id.is_of(self.arena.generation()), "stale handle");
+        let offsets = self.offsets_ref();
+        (offsets[id.index()], offsets[id.index() + 1])
+    }
+
+    #[inline]
+    pub(crate) fn offsets_ref(&self) -> &[usize] {
+        // SAFETY:
+        //
+        // The offset list is only mutated inside add_id(), which never
+        // runs concurrently with this shared access.
+        unsafe { &*self.offsets.get() }
+    }
+}
+
+/// Maximum number of strings printed by Debug.
+const DEBUG_LIMIT: usize = 16;
+
+impl fmt::Debug for IndexedStringArena {
+    /// Print stored strings in insertion order, omitting those beyond
+    /// the first 16.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Strings<'a>(&'a IndexedStringArena);
+
+        impl fmt::Debug for Strings<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let iter = self.0.iter();
+                let more = iter.len().saturating_sub(DEBUG_LIMIT);
+                let mut list = f.debug_list();
+                list.entries(iter.take(DEBUG_LIMIT));
+                if more > 0 {
+                    list.entry(&format_args!("... {} more", more));
+                }
+                list.finish()
+            }
+        }
+
+        f.debug_struct("IndexedStringArena")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("strings", &Strings(self))
+            .finish()
+    }
+}
+
+impl PartialEq for IndexedStringArena {
+    /// Compare stored strings of two arenas in insertion order.
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.offsets_ref() == other.offsets_ref() && self.values() == other.values()
+    }
+}
+
+impl Eq for IndexedStringArena {}
+
+impl Hash for IndexedStringArena {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.offsets_ref().hash(state);
+        self.values().hash(state);
+    }
+}
+
+impl<'a> IntoIterator for &'a IndexedStringArena {
+    type Item = &'a str;
+    type IntoIter = IndexedIter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> IndexedIter<'a> {
+        self.iter()
+    }
+}
+
+/// Index of strings discarded in [`RemapTable`].
+const NONE: u32 = u32::MAX;
+
+/// RemapTable maps handles of live strings to new handles, returned by
+/// [`IndexedStringArena::compact`].
+#[derive(Debug, Clone)]
+pub struct RemapTable {
+    // New index of each string before compaction, or NONE if discarded.
+    map: Vec<u32>,
+    from: u32,
+    to: u32,
+}
+
+impl RemapTable {
+    /// Returns the new handle of the string, or None if it is discarded
+    /// or the handle is not taken right before compaction.
+    #[inline]
+    pub fn get(&self, id: StrId) -> Option<StrId> {
+        if !id.is_of(self.from) {
+            return None;
+        }
+        match self.map.get(id.index()) {
+            Some(&index) if index != NONE => Some(StrId::new(index, self.to)),
+            _ => None,
+        }
+    }
+
+    /// Returns number of strings kept.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.iter().filter(|&&i| i != NONE).count()
+    }
+
+    /// Returns whether no string is kept.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Iterator over strings of [`IndexedStringArena`] in insertion order.
+///
+/// Strings added after the iterator is created are not visited.
+pub struct IndexedIter<'a> {
+    arena: &'a IndexedStringArena,
+    idx: usize,
+    end: usize,
+}
+
+impl<'a> Iterator for IndexedIter<'a> {
+    type Item = &'a str;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a str> {
+        if self.idx == self.end {
+            return None;
+        }
+        let res = self.arena.resolve(self.arena.id(self.idx));
+        self.idx += 1;
+        Some(res)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.end - self.idx;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for IndexedIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact() {
+        let mut ia = IndexedStringArena::with_capacity(32);
+        let ids: Vec<StrId> = ["dead", "alive", "", "gone", "kept"]
+            .iter()
+            .map(|s| ia.add_id(s).unwrap())
+            .collect();
+        let remap = ia.compact([ids[4], ids[1], ids[2], ids[1]]);
+        assert_eq!(remap.len(), 3);
+        assert_eq!(ia.len(), 9);
+        assert_eq!(ia.len_strings(), 3);
+        assert_eq!(ia.iter().collect::<Vec<_>>(), vec!["alive", "", "kept"]);
+        assert_eq!(remap.get(ids[0]), None);
+        assert_eq!(remap.get(ids[3]), None);
+        let id = remap.get(ids[4]).unwrap();
+        assert_eq!(id.index(), 2);
+        assert_eq!(ia.resolve(id), "kept");
+        assert_eq!(ia.resolve(remap.get(ids[1]).unwrap()), "alive");
+        assert_eq!(remap.get(id), None);
+        assert_eq!(ia.add("x".repeat(23)).unwrap().len(), 23);
+        assert!(ia.compact([]).is_empty());
+        assert!(ia.is_empty());
+    }
+
+    #[test]
+    fn test_indexed_string_arena() {
+        let ia = IndexedStringArena::with_capacity(12);
+        let id1 = ia.add_id("hello").unwrap();
+        let id2 = ia.add_id("").unwrap();
+        let s3 = ia.add("world").unwrap();
+        assert_eq!(id1.index(), 0);
+        assert_eq!(id2.index(), 1);
+        assert_eq!(ia.resolve(id1), "hello");
+        assert_eq!(ia.resolve(id2), "");
+        assert_eq!(s3, "world");
+        assert_eq!(std::mem::size_of::<StrId>(), 4);
+        assert!(ia.add_id("rust").is_err());
+        assert_eq!(ia.len(), 10);
+        let ia2 = ia.renew();
+        assert!(ia2.is_empty());
+        assert_eq!(ia2.add_id("rust").unwrap().index(), 0);
+    }
+
+    #[test]
+    fn test_indexed_string_arena_eq() {
+        let ia1 = IndexedStringArena::with_capacity(16);
+        let ia2 = IndexedStringArena::with_capacity(8);
+        ia1.add("ab").unwrap();
+        ia2.add("a").unwrap();
+        ia2.add("b").unwrap();
+        assert!(ia1 != ia2);
+        let ia3 = IndexedStringArena::with_capacity(2);
+        ia3.add("ab").unwrap();
+        assert!(ia1 == ia3);
+    }
+
+    #[test]
+    fn test_indexed_append_arena() {
+        let mut ia = IndexedStringArena::with_capacity(12);
+        ia.add("hello").unwrap();
+        let other = IndexedStringArena::with_capacity(8);
+        let id1 = other.add_id("ab").unwrap();
+        let id2 = other.add_id("cde").unwrap();
+        let remap = ia.append_arena(&other).unwrap();
+        assert_eq!(ia.resolve(remap(id1)), "ab");
+        assert_eq!(ia.resolve(remap(id2)), "cde");
+        assert_eq!(ia.iter().count(), 3);
+        assert!(ia.append_arena(&other).is_err());
+        assert_eq!(ia.iter().count(), 3);
+        let _ = ia.append_arena(&IndexedStringArena::with_capacity(0)).unwrap();
+        assert_eq!(ia.iter().count(), 3);
+    }
+
+    #[test]
+    fn test_indexed_append_empty_arenas() {
+        let mut ia = IndexedStringArena::with_capacity(0);
+        let _ = ia.append_arena(&IndexedStringArena::with_capacity(0)).unwrap();
+        assert!(ia.is_empty());
+        assert_eq!(ia.len_strings(), 0);
+    }
+
+    #[test]
+    fn test_indexed_span() {
+        let ia = IndexedStringArena::with_capacity(16);
+        ia.add("ab").unwrap();
+        let id = ia.add_id("token line").unwrap();
+        let span = ia.span(id);
+        assert_eq!(span, Span::new(2, 10));
+        assert_eq!(ia.resolve_span(span.slice(6..).unwrap()), Some("line"));
+        assert_eq!(ia.resolve_span(Span::new(8, 8)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "stale handle")]
+    fn test_indexed_stale_handle() {
+        let mut ia = IndexedStringArena::with_capacity(16);
+        let id = ia.add_id("hello").unwrap();
+        let span = ia.span(id);
+        ia.clear();
+        ia.add_id("world").unwrap();
+        assert_eq!(ia.resolve_span(span), None);
+        ia.resolve(id);
+    }
+
+    #[test]
+    fn test_str_id_generation() {
+        let mut ia = IndexedStringArena::with_capacity(16);
+        let id = ia.add_id("hello").unwrap();
+        assert_eq!(StrId::from_bits(id.to_bits()), id);
+        for i in 1..=16 {
+            ia.clear();
+            let fresh = ia.add_id("world").unwrap();
+            assert_eq!(fresh.generation(), i % 16);
+            assert_eq!(id.is_of(ia.generation()), i == 16);
+        }
+        assert_eq!(StrId::new(MAX_INDEX, 3).index(), MAX_INDEX as usize);
+    }
+
+    #[test]
+    fn test_indexed_string_arena_debug() {
+        let ia = IndexedStringArena::with_capacity(64);
+        ia.add("a").unwrap();
+        ia.add("b\"c").unwrap();
+        assert_eq!(
+            format!("{:?}", ia),
+            r#"IndexedStringArena { len: 4, capacity: 64, strings: ["a", "b\"c"] }"#
+        );
+        for _ in 0..DEBUG_LIMIT {
+            ia.add("x").unwrap();
+        }
+        assert!(format!("{:?}", ia).ends_with(r#""x", ... 2 more] }"#));
+    }
+
+    #[test]
+    fn test_indexed_string_arena_iter() {
+        let ia = IndexedStringArena::with_capacity(64);
+        for s in ["a", "bc", "", "def"] {
+            ia.add(s).unwrap();
+        }
+        let iter = ia.iter();
+        assert_eq!(iter.len(), 4);
+        ia.add("ignored").unwrap();
+        assert_eq!(iter.collect::<Vec<_>>(), vec!["a", "bc", "", "def"]);
+        assert_eq!((&ia).into_iter().count(), 5);
+        assert_eq!(ia.len_strings(), 5);
+        assert_eq!(ia.get(1), Some("bc"));
+        assert_eq!(ia.get(4), Some("ignored"));
+        assert_eq!(ia.get(5), None);
+    }
+}
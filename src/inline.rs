@@ -0,0 +1,113 @@
+use crate::{Error, Result};
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+
+/// InlineStringArena is a single-thread append-only string arena
+/// with fixed capacity, which stores its buffer inline.
+///
+/// No heap allocation is performed, so it is suitable for short-lived
+/// scratch space on the stack.
+pub struct InlineStringArena<const N: usize> {
+    buf: UnsafeCell<[MaybeUninit<u8>; N]>,
+    idx: Cell<usize>,
+}
+
+impl<const N: usize> InlineStringArena<N> {
+    /// Create a new inline string arena.
+    #[inline]
+    pub const fn new() -> Self {
+        InlineStringArena {
+            buf: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            idx: Cell::new(0),
+        }
+    }
+
+    /// Returns bytes written of current arena.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.idx.get()
+    }
+
+    /// Returns whether the arena is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns capacity of current arena.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Add a string into current arena.
+    /// Returns the string ref if succeeds.
+    /// The only reason of failure is that input string exceeds remained capacity.
+    /// The additional bytes required to store it is returned if fails.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        let bs = self.add_bytes(s.as_ref().as_bytes())?;
+        // SAFETY:
+        //
+        // The bytes are copied from valid UTF-8.
+        unsafe { Ok(core::str::from_utf8_unchecked(bs)) }
+    }
+
+    /// Add raw bytes into current arena.
+    /// Returns the byte slice ref if succeeds.
+    #[inline]
+    pub fn add_bytes<T: AsRef<[u8]>>(&self, bs: T) -> Result<&[u8]> {
+        let bs = bs.as_ref();
+        let len = bs.len();
+        let idx = self.len();
+        let new_len = len + idx;
+        if N < new_len {
+            return Err(Error::ExceedsCapacity(new_len - N));
+        }
+        // SAFETY:
+        //
+        // The region after idx is never referenced, and the input does not
+        // overlap with it.
+        unsafe {
+            let dst = (self.buf.get() as *mut u8).add(idx);
+            core::ptr::copy_nonoverlapping(bs.as_ptr(), dst, len);
+            self.idx.set(new_len);
+            Ok(core::slice::from_raw_parts(dst, len))
+        }
+    }
+
+    /// Clear the arena so its memory can be reused.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.idx.set(0);
+    }
+}
+
+impl<const N: usize> Default for InlineStringArena<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_string_arena() {
+        let mut sa = InlineStringArena::<12>::new();
+        assert_eq!(sa.capacity(), 12);
+        let s1 = sa.add("hello").unwrap();
+        let s2 = sa.add("world").unwrap();
+        assert_eq!(s1, "hello");
+        assert_eq!(s2, "world");
+        assert!(matches!(sa.add("rust"), Err(Error::ExceedsCapacity(2))));
+        sa.clear();
+        assert!(sa.is_empty());
+        assert_eq!(sa.add("rust").unwrap(), "rust");
+        let empty = InlineStringArena::<0>::default();
+        assert_eq!(empty.add("").unwrap(), "");
+    }
+}
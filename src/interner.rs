@@ -0,0 +1,451 @@
+use crate::{Result, Span, StringArena};
+use std::cell::UnsafeCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+/// CaseFolding decides whether strings differing only in case are
+/// interned as the same string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseFolding {
+    /// Strings are compared exactly.
+    #[default]
+    None,
+    /// ASCII letters are compared case-insensitively.
+    Ascii,
+    /// All chars are compared by their Unicode lowercase mapping. This is
+    /// not full Unicode case folding, e.g. "ß" and "SS" differ.
+    Lowercase,
+}
+
+/// Folded is a string hashed according to the case folding.
+struct Folded<'a>(&'a str, CaseFolding);
+
+impl Hash for Folded<'_> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.1 {
+            CaseFolding::None => self.0.hash(state),
+            CaseFolding::Ascii => {
+                for b in self.0.bytes() {
+                    state.write_u8(b.to_ascii_lowercase());
+                }
+                state.write_u8(0xff);
+            }
+            CaseFolding::Lowercase => {
+                for c in self.0.chars().flat_map(char::to_lowercase) {
+                    state.write_u32(c as u32);
+                }
+                state.write_u8(0xff);
+            }
+        }
+    }
+}
+
+/// Compare two strings according to the case folding.
+#[inline]
+fn folded_eq(a: &str, b: &str, folding: CaseFolding) -> bool {
+    match folding {
+        CaseFolding::None => a == b,
+        CaseFolding::Ascii => a.eq_ignore_ascii_case(b),
+        CaseFolding::Lowercase => a
+            .chars()
+            .flat_map(char::to_lowercase)
+            .eq(b.chars().flat_map(char::to_lowercase)),
+    }
+}
+
+/// Table is an open-addressed hash table with linear probing, which
+/// stores the hash and span of each interned string.
+///
+/// Strings are compared against the arena by span, so no key is
+/// constructed for lookup and stored hashes are never recomputed.
+struct Table {
+    // Tag of each slot, which is the hash with highest bit set, or zero
+    // for an empty slot. Length is zero or a power of two.
+    slots: Vec<(u64, Span)>,
+    len: usize,
+}
+
+impl Table {
+    #[inline]
+    fn new() -> Self {
+        Table {
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    #[inline]
+    fn tag(hash: u64) -> u64 {
+        hash | (1 << 63)
+    }
+
+    /// Returns the span of the first entry with given hash which
+    /// satisfies the predicate.
+    #[inline]
+    fn find(&self, hash: u64, mut eq: impl FnMut(Span) -> bool) -> Option<Span> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let tag = Self::tag(hash);
+        let mask = self.slots.len() - 1;
+        let mut i = tag as usize & mask;
+        loop {
+            let (t, span) = self.slots[i];
+            if t == 0 {
+                return None;
+            }
+            if t == tag && eq(span) {
+                return Some(span);
+            }
+            i = (i + 1) & mask;
+        }
+    }
+
+    /// Insert a new entry, which must not exist in the table.
+    #[inline]
+    fn insert(&mut self, hash: u64, span: Span) {
+        // Keep load factor at most 3/4, so probing always terminates.
+        if (self.len + 1) * 4 > self.slots.len() * 3 {
+            self.grow();
+        }
+        let tag = Self::tag(hash);
+        let mask = self.slots.len() - 1;
+        let mut i = tag as usize & mask;
+        while self.slots[i].0 != 0 {
+            i = (i + 1) & mask;
+        }
+        self.slots[i] = (tag, span);
+        self.len += 1;
+    }
+
+    fn grow(&mut self) {
+        let new_cap = (self.slots.len() * 2).max(16);
+        let old = core::mem::replace(&mut self.slots, vec![(0, Span::default()); new_cap]);
+        let mask = new_cap - 1;
+        for (tag, span) in old.into_iter().filter(|(t, _)| *t != 0) {
+            let mut i = tag as usize & mask;
+            while self.slots[i].0 != 0 {
+                i = (i + 1) & mask;
+            }
+            self.slots[i] = (tag, span);
+        }
+    }
+
+    #[inline]
+    fn iter(&self) -> impl Iterator<Item = Span> + '_ {
+        self.slots.iter().filter(|(t, _)| *t != 0).map(|(_, span)| *span)
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.slots.fill((0, Span::default()));
+        self.len = 0;
+    }
+}
+
+/// StringInterner is a single-thread string interner backed by [`StringArena`].
+///
+/// Identical strings are stored only once, and all lookups of the same
+/// content return the same string ref. With [`CaseFolding`], strings
+/// differing only in case are identical, and the spelling seen first is
+/// returned for all of them.
+///
+/// Strings are hashed by `S`, which is [`RandomState`] by default.
+pub struct StringInterner<S = RandomState> {
+    table: UnsafeCell<Table>,
+    arena: StringArena,
+    folding: CaseFolding,
+    hasher: S,
+}
+
+impl StringInterner {
+    /// Create a new string interner with given arena capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::try_with_capacity(cap).unwrap()
+    }
+
+    /// Try to create a new string interner with given arena capacity.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity(cap: usize) -> Result<Self> {
+        Self::try_with_capacity_and_folding(cap, CaseFolding::None)
+    }
+
+    /// Create a new string interner with given arena capacity and case folding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity_and_folding(cap: usize, folding: CaseFolding) -> Self {
+        Self::try_with_capacity_and_folding(cap, folding).unwrap()
+    }
+
+    /// Try to create a new string interner with given arena capacity and
+    /// case folding.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity_and_folding(cap: usize, folding: CaseFolding) -> Result<Self> {
+        Self::try_with_capacity_and_hasher(cap, folding, RandomState::new())
+    }
+}
+
+impl<S: BuildHasher> StringInterner<S> {
+    /// Create a new string interner with given arena capacity, case folding
+    /// and hasher.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity_and_hasher(cap: usize, folding: CaseFolding, hasher: S) -> Self {
+        Self::try_with_capacity_and_hasher(cap, folding, hasher).unwrap()
+    }
+
+    /// Try to create a new string interner with given arena capacity, case
+    /// folding and hasher.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity_and_hasher(
+        cap: usize,
+        folding: CaseFolding,
+        hasher: S,
+    ) -> Result<Self> {
+        Ok(StringInterner {
+            table: UnsafeCell::new(Table::new()),
+            arena: StringArena::try_with_capacity(cap)?,
+            folding,
+            hasher,
+        })
+    }
+
+    /// Returns the case folding of this interner.
+    #[inline]
+    pub fn case_folding(&self) -> CaseFolding {
+        self.folding
+    }
+
+    /// Returns the hasher of this interner.
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Returns hash of the string used by this interner.
+    /// Without case folding, it equals to hashing the string by a hasher
+    /// built from [`hasher`](Self::hasher), so it can be computed in advance.
+    #[inline]
+    pub fn hash_str(&self, s: &str) -> u64 {
+        self.hasher.hash_one(Folded(s, self.folding))
+    }
+
+    /// Returns number of distinct strings interned.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.table_ref().len
+    }
+
+    /// Returns whether the interner is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the underlying arena.
+    #[inline]
+    pub fn arena(&self) -> &StringArena {
+        &self.arena
+    }
+
+    /// Returns the interned string ref if exists.
+    #[inline]
+    pub fn get<T: AsRef<str>>(&self, s: T) -> Option<&str> {
+        let s = s.as_ref();
+        self.get_prehashed(self.hash_str(s), s)
+    }
+
+    /// Returns the interned string ref if exists, with hash of the string
+    /// computed in advance.
+    /// The hash must equal to [`hash_str`](Self::hash_str), otherwise
+    /// the string may not be found.
+    #[inline]
+    pub fn get_prehashed(&self, hash: u64, s: &str) -> Option<&str> {
+        self.table_ref()
+            .find(hash, |span| folded_eq(self.resolve(span), s, self.folding))
+            .map(|span| self.resolve(span))
+    }
+
+    /// Returns the interned string ref, adding it into arena if not exists.
+    /// Fails only if the arena exceeds capacity.
+    #[inline]
+    pub fn get_or_intern<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        let s = s.as_ref();
+        self.intern_prehashed(self.hash_str(s), s)
+    }
+
+    /// Returns the interned string ref, adding it into arena if not exists,
+    /// with hash of the string computed in advance, so hashing is skipped.
+    /// The hash must equal to [`hash_str`](Self::hash_str), otherwise
+    /// the string may be interned more than once.
+    /// Fails only if the arena exceeds capacity.
+    #[inline]
+    pub fn intern_prehashed(&self, hash: u64, s: &str) -> Result<&str> {
+        if let Some(res) = self.get_prehashed(hash, s) {
+            return Ok(res);
+        }
+        let span = self.arena.add_span(s)?;
+        // SAFETY:
+        //
+        // The table is guaranteed not to be accessed concurrently,
+        // and no reference to the table is alive here.
+        unsafe { (*self.table.get()).insert(hash, span) };
+        Ok(self.resolve(span))
+    }
+
+    /// Intern all strings of another interner into current interner.
+    /// Strings already interned are not copied again.
+    /// Fails only if the arena exceeds capacity, and strings interned
+    /// before the failure are kept.
+    #[inline]
+    pub fn append_interner<S2: BuildHasher>(
+        &mut self,
+        other: &StringInterner<S2>,
+    ) -> Result<()> {
+        for span in other.table_ref().iter() {
+            self.get_or_intern(other.resolve(span))?;
+        }
+        Ok(())
+    }
+
+    /// Clear the interner so its memory can be reused.
+    /// The exclusive borrow guarantees no string ref of this interner is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.table.get_mut().clear();
+        self.arena.clear();
+    }
+
+    /// Renew the interner for future usage.
+    /// This is safe because all string refs associated to this interner
+    /// must be dropped before this method call.
+    #[inline]
+    pub fn renew(mut self) -> Self {
+        self.clear();
+        self
+    }
+
+    #[inline]
+    fn table_ref(&self) -> &Table {
+        // SAFETY:
+        //
+        // The table is only mutated inside intern_prehashed(), which never
+        // runs concurrently with this shared access.
+        unsafe { &*self.table.get() }
+    }
+
+    #[inline]
+    fn resolve(&self, span: Span) -> &str {
+        // SAFETY:
+        //
+        // All spans in the table are written by intern_prehashed() and
+        // are valid UTF-8.
+        unsafe { self.arena.slice_unchecked(span.offset(), span.end()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_interner() {
+        let si = StringInterner::with_capacity(12);
+        assert!(si.is_empty());
+        let s1 = si.get_or_intern("hello").unwrap();
+        let s2 = si.get_or_intern(String::from("hello")).unwrap();
+        assert_eq!(s1.as_ptr(), s2.as_ptr());
+        assert_eq!(si.len(), 1);
+        assert_eq!(si.arena().len(), 5);
+        assert_eq!(si.get("hello"), Some("hello"));
+        assert_eq!(si.get("world"), None);
+        si.get_or_intern("world").unwrap();
+        assert!(si.get_or_intern("rust").is_err());
+        assert_eq!(si.len(), 2);
+        let si2 = si.renew();
+        assert!(si2.is_empty());
+        assert!(si2.arena().is_empty());
+    }
+
+    #[test]
+    fn test_case_folding() {
+        let si = StringInterner::with_capacity_and_folding(32, CaseFolding::Ascii);
+        assert_eq!(si.case_folding(), CaseFolding::Ascii);
+        let s1 = si.get_or_intern("Content-Type").unwrap();
+        let s2 = si.get_or_intern("content-type").unwrap();
+        assert_eq!(s1.as_ptr(), s2.as_ptr());
+        assert_eq!(si.get("CONTENT-TYPE"), Some("Content-Type"));
+        assert_eq!(si.get("Straße"), None);
+        si.get_or_intern("Straße").unwrap();
+        assert_eq!(si.get("STRAßE"), Some("Straße"));
+        assert_eq!(si.get("STRASSE"), None);
+        assert_eq!(si.len(), 2);
+
+        let si = StringInterner::with_capacity_and_folding(32, CaseFolding::Lowercase);
+        si.get_or_intern("Ärger").unwrap();
+        assert_eq!(si.get("äRGER"), Some("Ärger"));
+        assert_eq!(si.get("arger"), None);
+        si.get_or_intern("ß").unwrap();
+        assert_eq!(si.get("SS"), None);
+        let si = StringInterner::with_capacity(32);
+        si.get_or_intern("Ärger").unwrap();
+        assert_eq!(si.get("ärger"), None);
+    }
+
+    #[test]
+    fn test_intern_prehashed() {
+        let si = StringInterner::with_capacity(32);
+        let hash = si.hasher().hash_one("hello");
+        assert_eq!(hash, si.hash_str("hello"));
+        let s1 = si.intern_prehashed(hash, "hello").unwrap();
+        assert_eq!(si.get("hello").unwrap().as_ptr(), s1.as_ptr());
+        assert_eq!(si.get_prehashed(hash, "hello"), Some("hello"));
+        let si =
+            StringInterner::with_capacity_and_hasher(32, CaseFolding::Ascii, RandomState::new());
+        let hash = si.hash_str("Host");
+        si.intern_prehashed(hash, "Host").unwrap();
+        assert_eq!(si.get("hOST"), Some("Host"));
+    }
+
+    #[test]
+    fn test_interner_growth() {
+        let si = StringInterner::with_capacity(8192);
+        let strs: Vec<&str> = (0..1000)
+            .map(|i| si.get_or_intern(format!("s{}", i)).unwrap())
+            .collect();
+        assert_eq!(si.len(), 1000);
+        for (i, s) in strs.iter().enumerate() {
+            assert_eq!(si.get(format!("s{}", i)).unwrap().as_ptr(), s.as_ptr());
+        }
+        assert_eq!(si.get("s1000"), None);
+    }
+
+    #[test]
+    fn test_append_interner() {
+        let mut si = StringInterner::with_capacity(8);
+        si.get_or_intern("hello").unwrap();
+        let other = StringInterner::with_capacity(16);
+        other.get_or_intern("hello").unwrap();
+        other.get_or_intern("abc").unwrap();
+        si.append_interner(&other).unwrap();
+        assert_eq!(si.len(), 2);
+        assert_eq!(si.arena().len(), 8);
+        other.get_or_intern("rust").unwrap();
+        assert!(si.append_interner(&other).is_err());
+    }
+}
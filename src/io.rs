@@ -0,0 +1,211 @@
+//! Ingestion from readers, which requires `std`.
+use crate::{BackingAlloc, Error, Result, Span, StringArena};
+use core::alloc::Layout;
+use std::io::{BufRead, ErrorKind, Read};
+
+/// Maximum bytes requested from the reader at once.
+const READ_CHUNK: usize = 8192;
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Read all bytes from the reader directly into current arena.
+    /// Returns the byte slice ref if succeeds.
+    /// Fails if the reader fails, or if the reader still has data after
+    /// capacity is exhausted. Nothing is kept in the arena on failure,
+    /// but the reader may be partially consumed.
+    pub fn add_from_reader<R: Read + ?Sized>(&self, r: &mut R) -> Result<&[u8]> {
+        let start = self.len();
+        // End of the region already zeroed, so short reads do not zero
+        // the same bytes again.
+        let mut zeroed = start;
+        let res = loop {
+            let pos = self.len();
+            let n = self.remaining().min(READ_CHUNK);
+            if n == 0 {
+                // Probe whether the reader is at EOF.
+                match r.read(&mut [0u8; 1]) {
+                    Ok(0) => break Ok(()),
+                    Ok(_) => break Err(self.reject(1, 1)),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => break Err(Error::Io(e)),
+                }
+            }
+            let ptr = self.reserve_layout(Layout::from_size_align(n, 1).unwrap())?;
+            // SAFETY:
+            //
+            // The region is reserved above and not exposed, bytes after
+            // the zeroed mark are initialized before creating the slice.
+            let buf = unsafe {
+                if zeroed < pos + n {
+                    let from = zeroed.max(pos);
+                    core::ptr::write_bytes(ptr.as_ptr().add(from - pos), 0, pos + n - from);
+                    zeroed = pos + n;
+                }
+                core::slice::from_raw_parts_mut(ptr.as_ptr(), n)
+            };
+            let read = r.read(buf);
+            // SAFETY:
+            //
+            // The buffer is not used any more.
+            match read {
+                Ok(0) => {
+                    unsafe { self.set_len(pos) };
+                    break Ok(());
+                }
+                Ok(k) => unsafe { self.set_len(pos + k.min(n)) },
+                Err(e) if e.kind() == ErrorKind::Interrupted => unsafe { self.set_len(pos) },
+                Err(e) => break Err(Error::Io(e)),
+            }
+        };
+        match res {
+            Ok(()) => {
+                self.record(start);
+                // SAFETY:
+                //
+                // The region is written above.
+                Ok(unsafe { self.bytes_unchecked(start, self.len()) })
+            }
+            Err(e) => {
+                // SAFETY:
+                //
+                // The bytes read are not exposed.
+                unsafe { self.set_len(start) };
+                Err(e)
+            }
+        }
+    }
+
+    /// Read all bytes from the reader directly into current arena,
+    /// validating they are UTF-8.
+    /// Returns the string ref if succeeds.
+    /// Fails with length of the valid prefix if input is invalid, and
+    /// nothing is kept in the arena.
+    pub fn add_utf8_from_reader<R: Read + ?Sized>(&self, r: &mut R) -> Result<&str> {
+        let start = self.len();
+        let bs = self.add_from_reader(r)?;
+        match core::str::from_utf8(bs) {
+            Ok(s) => Ok(s),
+            Err(e) => {
+                // SAFETY:
+                //
+                // The bytes read are not exposed.
+                unsafe { self.set_len(start) };
+                Err(Error::InvalidUtf8(e.valid_up_to()))
+            }
+        }
+    }
+
+    /// Read all lines from the reader directly into current arena, each as
+    /// a separate string without the terminator `\n` or `\r\n`.
+    /// Returns spans of the lines if succeeds.
+    /// Fails if the reader fails, the arena exceeds capacity, or any line
+    /// is not UTF-8, with length of the valid prefix of the input. Nothing
+    /// is kept in the arena on failure, but the reader may be partially
+    /// consumed.
+    pub fn add_lines<R: BufRead>(&self, mut r: R) -> Result<Vec<Span>> {
+        let start = self.len();
+        let mut spans = Vec::new();
+        // Bytes consumed from the reader, including terminators.
+        let mut input = 0;
+        let res = loop {
+            match self.read_line(&mut r, &mut input) {
+                Ok(Some(span)) => spans.push(span),
+                Ok(None) => break Ok(spans),
+                Err(e) => break Err(e),
+            }
+        };
+        if res.is_err() {
+            // SAFETY:
+            //
+            // The lines read are not exposed.
+            unsafe { self.set_len(start) };
+        }
+        res
+    }
+
+    /// Read a line from the reader into current arena, counting bytes
+    /// consumed.
+    /// Returns None if the reader is at EOF.
+    fn read_line<R: BufRead>(&self, r: &mut R, input: &mut usize) -> Result<Option<Span>> {
+        let (start, line) = (self.len(), *input);
+        let (mut newline, mut read) = (false, false);
+        while !newline {
+            let buf = match r.fill_buf() {
+                Ok([]) => break,
+                Ok(buf) => buf,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::Io(e)),
+            };
+            let n = match buf.iter().position(|&b| b == b'\n') {
+                Some(i) => {
+                    newline = true;
+                    self.push_bytes(&buf[..i])?;
+                    i + 1
+                }
+                None => {
+                    self.push_bytes(buf)?;
+                    buf.len()
+                }
+            };
+            r.consume(n);
+            *input += n;
+            read = true;
+        }
+        if !read {
+            return Ok(None);
+        }
+        // SAFETY:
+        //
+        // The region is written above.
+        let mut bs = unsafe { self.bytes_unchecked(start, self.len()) };
+        if newline && bs.last() == Some(&b'\r') {
+            bs = &bs[..bs.len() - 1];
+            // SAFETY:
+            //
+            // The carriage return is not exposed.
+            unsafe { self.set_len(start + bs.len()) };
+        }
+        core::str::from_utf8(bs).map_err(|e| Error::InvalidUtf8(line + e.valid_up_to()))?;
+        self.record(start);
+        Ok(Some(Span::tagged(start, bs.len(), self.generation())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, StringArena};
+    use std::io::Read;
+
+    #[test]
+    fn test_add_from_reader() {
+        let sa = StringArena::with_capacity(16);
+        let bs = sa.add_from_reader(&mut &b"\xffhello"[..]).unwrap();
+        assert_eq!(bs, b"\xffhello");
+        // Short reads are continued until EOF.
+        let mut r = (&b"wor"[..]).chain(&b"ld"[..]);
+        assert_eq!(sa.add_utf8_from_reader(&mut r).unwrap(), "world");
+        assert!(sa.add_utf8_from_reader(&mut &b"\xff"[..]).is_err());
+        assert_eq!(sa.len(), 11);
+        assert!(sa.add_from_reader(&mut &b"too long"[..]).is_err());
+        assert_eq!(sa.len(), 11);
+        assert_eq!(sa.add_from_reader(&mut &b"exact"[..]).unwrap(), b"exact");
+        assert_eq!(sa.len(), 16);
+    }
+
+    #[test]
+    fn test_add_lines() {
+        let sa = StringArena::with_capacity(32);
+        sa.add("x").unwrap();
+        // A tiny buffer makes lines span multiple reads.
+        let r = std::io::BufReader::with_capacity(3, &b"alpha\r\n\nbeta\ngam\rma"[..]);
+        let spans = sa.add_lines(r).unwrap();
+        let lines: Vec<&str> = spans.iter().map(|&s| sa.get(s).unwrap()).collect();
+        assert_eq!(lines, vec!["alpha", "", "beta", "gam\rma"]);
+        assert_eq!(spans[0].offset(), 1);
+        assert_eq!(sa.add_lines(&b"one\n"[..]).unwrap().len(), 1);
+        assert!(sa.add_lines(&b""[..]).unwrap().is_empty());
+        let len = sa.len();
+        assert!(matches!(sa.add_lines(&b"ok\nb\xffd\n"[..]), Err(Error::InvalidUtf8(4))));
+        assert!(sa.add_lines(&b"a long line over capacity\n"[..]).is_err());
+        assert_eq!(sa.len(), len);
+    }
+}
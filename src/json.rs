@@ -0,0 +1,401 @@
+//! JSON document model stored in arenas, which requires feature `json`.
+use crate::{BackingAlloc, Error, Result, StringArena};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// Maximum nesting depth of arrays and objects.
+const MAX_DEPTH: usize = 128;
+
+/// ArenaJsonValue is a JSON value whose strings, keys and nodes are all
+/// stored in a [`StringArena`], returned by [`StringArena::parse_json`].
+///
+/// It holds only refs into the arena, so dropping a document is free,
+/// and all documents are released with the arena at once. Numbers are
+/// kept as their text, so no precision is lost before conversion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArenaJsonValue<'a> {
+    Null,
+    Bool(bool),
+    Number(&'a str),
+    String(&'a str),
+    Array(&'a [ArenaJsonValue<'a>]),
+    Object(&'a [(&'a str, ArenaJsonValue<'a>)]),
+}
+
+impl<'a> ArenaJsonValue<'a> {
+    /// Returns whether the value is null.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        matches!(self, ArenaJsonValue::Null)
+    }
+
+    /// Returns the boolean if the value is one.
+    #[inline]
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            ArenaJsonValue::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the number as `f64` if the value is one.
+    #[inline]
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            ArenaJsonValue::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number as `i64` if the value is an integer in range.
+    #[inline]
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ArenaJsonValue::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the string if the value is one.
+    #[inline]
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            ArenaJsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns elements if the value is an array.
+    #[inline]
+    pub fn as_array(&self) -> Option<&'a [ArenaJsonValue<'a>]> {
+        match *self {
+            ArenaJsonValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Returns members in document order if the value is an object.
+    #[inline]
+    pub fn as_object(&self) -> Option<&'a [(&'a str, ArenaJsonValue<'a>)]> {
+        match *self {
+            ArenaJsonValue::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the last member of given key if the value is
+    /// an object.
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&'a ArenaJsonValue<'a>> {
+        self.as_object()?.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+}
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Parse a JSON document, storing all its strings and nodes into
+    /// current arena.
+    /// Returns the root value if succeeds.
+    /// Fails with the position of the first invalid byte, or if the arena
+    /// exceeds capacity. Nothing is kept in the arena on failure.
+    pub fn parse_json<T: AsRef<[u8]>>(&self, input: T) -> Result<ArenaJsonValue<'_>> {
+        let input = input.as_ref();
+        core::str::from_utf8(input).map_err(|e| Error::InvalidUtf8(e.valid_up_to()))?;
+        let start = self.len();
+        let mut p = Parser {
+            arena: self,
+            input,
+            pos: 0,
+            values: Vec::new(),
+            members: Vec::new(),
+        };
+        let res = p.value(0).and_then(|res| {
+            p.skip_ws();
+            if p.pos != input.len() {
+                return Err(Error::InvalidJson(p.pos));
+            }
+            Ok(res)
+        });
+        if res.is_err() {
+            drop(p);
+            // SAFETY:
+            //
+            // Values parsed are dropped with the parser, so nothing stored
+            // since start is exposed.
+            unsafe { self.set_len(start) };
+        }
+        res
+    }
+}
+
+/// Recursive descent parser of JSON over validated UTF-8.
+struct Parser<'a, 'i, A: BackingAlloc> {
+    arena: &'a StringArena<A>,
+    input: &'i [u8],
+    pos: usize,
+    // Pending elements and members of all open containers, which are
+    // moved into the arena when the container closes.
+    values: Vec<ArenaJsonValue<'a>>,
+    members: Vec<(&'a str, ArenaJsonValue<'a>)>,
+}
+
+impl<'a, A: BackingAlloc> Parser<'a, '_, A> {
+    #[inline]
+    fn err<T>(&self) -> Result<T> {
+        Err(Error::InvalidJson(self.pos))
+    }
+
+    #[inline]
+    fn skip_ws(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume given byte after whitespaces.
+    #[inline]
+    fn eat(&mut self, b: u8) -> bool {
+        self.skip_ws();
+        let res = self.input.get(self.pos) == Some(&b);
+        self.pos += usize::from(res);
+        res
+    }
+
+    fn value(&mut self, depth: usize) -> Result<ArenaJsonValue<'a>> {
+        self.skip_ws();
+        let res = match self.input.get(self.pos) {
+            Some(b'{') | Some(b'[') if depth == MAX_DEPTH => return self.err(),
+            Some(b'{') => {
+                self.pos += 1;
+                let base = self.members.len();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_ws();
+                        if self.input.get(self.pos) != Some(&b'"') {
+                            return self.err();
+                        }
+                        let key = self.string()?;
+                        if !self.eat(b':') {
+                            return self.err();
+                        }
+                        let value = self.value(depth + 1)?;
+                        self.members.push((key, value));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return self.err();
+                        }
+                    }
+                }
+                let res = self.arena.add_slice(&self.members[base..])?;
+                self.members.truncate(base);
+                ArenaJsonValue::Object(res)
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let base = self.values.len();
+                if !self.eat(b']') {
+                    loop {
+                        let value = self.value(depth + 1)?;
+                        self.values.push(value);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return self.err();
+                        }
+                    }
+                }
+                let res = self.arena.add_slice(&self.values[base..])?;
+                self.values.truncate(base);
+                ArenaJsonValue::Array(res)
+            }
+            Some(b'"') => ArenaJsonValue::String(self.string()?),
+            Some(b'-' | b'0'..=b'9') => ArenaJsonValue::Number(self.number()?),
+            _ => {
+                for (lit, v) in [
+                    (&b"null"[..], ArenaJsonValue::Null),
+                    (b"true", ArenaJsonValue::Bool(true)),
+                    (b"false", ArenaJsonValue::Bool(false)),
+                ] {
+                    if self.input[self.pos..].starts_with(lit) {
+                        self.pos += lit.len();
+                        return Ok(v);
+                    }
+                }
+                return self.err();
+            }
+        };
+        Ok(res)
+    }
+
+    /// Parse a number, validated by the grammar of JSON.
+    fn number(&mut self) -> Result<&'a str> {
+        let start = self.pos;
+        let digits = |p: &mut Self| {
+            let from = p.pos;
+            while let Some(b'0'..=b'9') = p.input.get(p.pos) {
+                p.pos += 1;
+            }
+            p.pos - from
+        };
+        self.pos += usize::from(self.input[self.pos] == b'-');
+        match digits(self) {
+            0 => return self.err(),
+            n if n > 1 && self.input[self.pos - n] == b'0' => return Err(Error::InvalidJson(start)),
+            _ => (),
+        }
+        if self.input.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if digits(self) == 0 {
+                return self.err();
+            }
+        }
+        if let Some(b'e' | b'E') = self.input.get(self.pos) {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.input.get(self.pos) {
+                self.pos += 1;
+            }
+            if digits(self) == 0 {
+                return self.err();
+            }
+        }
+        // SAFETY:
+        //
+        // The input is validated UTF-8, and the number is ASCII.
+        self.arena.add(unsafe { core::str::from_utf8_unchecked(&self.input[start..self.pos]) })
+    }
+
+    /// Parse a string starting with the quote, unescaped into the arena.
+    fn string(&mut self) -> Result<&'a str> {
+        self.pos += 1;
+        let mut w = self.arena.writer();
+        loop {
+            let start = self.pos;
+            while let Some(&b) = self.input.get(self.pos) {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            // SAFETY:
+            //
+            // The input is validated UTF-8, and the run ends before an
+            // ASCII byte or at the end.
+            let run = unsafe { core::str::from_utf8_unchecked(&self.input[start..self.pos]) };
+            let _ = w.write_str(run);
+            match self.input.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return w.finish();
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = match self.input.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode()?,
+                        _ => return self.err(),
+                    };
+                    self.pos += 1;
+                    let _ = w.write_char(c);
+                }
+                _ => return self.err(),
+            }
+        }
+    }
+
+    /// Parse the escape `\uXXXX` at `u`, followed by the low surrogate if
+    /// it is a high surrogate. Position is left at the last hex digit.
+    fn unicode(&mut self) -> Result<char> {
+        let hex = |p: &Self, at: usize| {
+            let bs = p.input.get(at..at + 4)?;
+            // SAFETY:
+            //
+            // The input is validated UTF-8, and a hex number is ASCII.
+            let s = unsafe { core::str::from_utf8_unchecked(bs) };
+            s.bytes().all(|b| b.is_ascii_hexdigit()).then(|| u32::from_str_radix(s, 16).ok())?
+        };
+        let Some(hi) = hex(self, self.pos + 1) else {
+            return self.err();
+        };
+        self.pos += 4;
+        if !(0xd800..0xdc00).contains(&hi) {
+            return char::from_u32(hi).map_or_else(|| self.err(), Ok);
+        }
+        if self.input.get(self.pos + 1..self.pos + 3) != Some(b"\\u") {
+            return self.err();
+        }
+        match hex(self, self.pos + 3) {
+            Some(lo) if (0xdc00..0xe000).contains(&lo) => {
+                self.pos += 6;
+                Ok(char::from_u32(0x10000 + ((hi - 0xd800) << 10) + (lo - 0xdc00)).unwrap())
+            }
+            _ => self.err(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let sa = StringArena::with_capacity(1024);
+        let doc = sa
+            .parse_json(concat!(
+                r#" {"id": 42, "name": "a\"b\u4e2d\ud83d\ude00", "tags": ["x", null, -1.5e3],"#,
+                r#" "ok": true, "nested": {"empty": [], "obj": {}}, "id": 7} "#,
+            ))
+            .unwrap();
+        assert_eq!(doc.as_object().unwrap().len(), 6);
+        assert_eq!(doc.get("id").unwrap().as_i64(), Some(7));
+        assert_eq!(doc.get("name").unwrap().as_str(), Some("a\"b中😀"));
+        let tags = doc.get("tags").unwrap().as_array().unwrap();
+        assert_eq!(tags[0], ArenaJsonValue::String("x"));
+        assert!(tags[1].is_null());
+        assert_eq!(tags[2].as_f64(), Some(-1500.0));
+        assert_eq!(tags[2].as_i64(), None);
+        assert_eq!(doc.get("ok").unwrap().as_bool(), Some(true));
+        let nested = doc.get("nested").unwrap();
+        assert_eq!(nested.get("empty").unwrap().as_array(), Some(&[][..]));
+        assert_eq!(nested.get("obj").unwrap().as_object().map(|o| o.len()), Some(0));
+        assert_eq!(doc.get("missing"), None);
+        assert_eq!(sa.parse_json(b"\"x\"").unwrap(), ArenaJsonValue::String("x"));
+    }
+
+    #[test]
+    fn test_parse_invalid_json() {
+        let sa = StringArena::with_capacity(1024);
+        let pos = |s: &str| match sa.parse_json(s) {
+            Err(Error::InvalidJson(pos)) => pos,
+            res => panic!("unexpected {:?}", res),
+        };
+        assert_eq!(pos(""), 0);
+        assert_eq!(pos("[1,]"), 3);
+        assert_eq!(pos("{\"a\" 1}"), 5);
+        assert_eq!(pos("01"), 0);
+        assert_eq!(pos("1."), 2);
+        assert_eq!(pos("\"\\x\""), 2);
+        assert_eq!(pos("\"\\ud800\""), 6);
+        assert_eq!(pos("\"open"), 5);
+        assert_eq!(pos("nul"), 0);
+        assert_eq!(pos("[] []"), 3);
+        assert_eq!(pos(&"[".repeat(200)), MAX_DEPTH);
+        assert!(matches!(sa.parse_json(b"\"\xff\""), Err(Error::InvalidUtf8(1))));
+        assert!(sa.is_empty());
+        sa.parse_json("[\"kept\"]").unwrap();
+        let len = sa.len();
+        assert_eq!(pos("{\"key\": [\"value\", 1], \"b\": x}"), 27);
+        assert_eq!(sa.len(), len);
+        let small = StringArena::with_capacity(4);
+        assert!(matches!(small.parse_json("\"hello\""), Err(Error::ExceedsCapacity(_))));
+    }
+}
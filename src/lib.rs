@@ -1,108 +1,195 @@
 //! AOSA represents Append-Only String Arena, it's convenient to hold plenty of temporary
 //! strings inside the continuous memory and free them all at once.
-use std::alloc::{alloc, Layout};
-use std::mem::align_of;
-use std::cell::{Cell, UnsafeCell};
-use thiserror::Error;
+//!
+//! The crate is `no_std` compatible with `alloc` if default feature `std` is disabled.
+//! Feature `nightly-allocator` implements the unstable `Allocator` trait for
+//! [`StringArena`], which requires a nightly compiler.
+//! Feature `mmap` enables arenas backed by anonymous memory mapping on Unix,
+//! and opening saved arenas by file mapping.
+//! Feature `mlock` enables locking [`SecretStringArena`] into memory on Unix.
+//! Feature `poison` fills discarded bytes with `POISON` for debugging, so
+//! use of strings after clear is easy to spot.
+//! Feature `csv` enables reading CSV records into arenas.
+//! Feature `json` enables parsing JSON documents into arenas.
+//! Feature `lz4` enables saving arenas with bytes of strings compressed, and
+//! opening them by mapping on Unix.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "nightly-allocator", feature(allocator_api))]
 
-pub type Result<T> = std::result::Result<T, Error>;
+extern crate alloc;
 
-#[derive(Debug, Error)]
-pub enum Error {
-    #[error("exceeds capacity with additional {0} bytes")]
-    ExceedsCapacity(usize),
-}
-
-/// StringArena is a single-thread append-only string arena.
-pub struct StringArena {
-    arena: UnsafeCell<Box<[u8]>>,
-    idx: Cell<usize>,
-}
-
-impl StringArena {
-    /// Create a new string arena with given capacity.
-    #[inline]
-    pub fn with_capacity(cap: usize) -> Self {
-        let layout = Layout::from_size_align(cap, align_of::<u8>()).unwrap();
-        let arena = unsafe {
-            let ptr = alloc(layout);
-            let vec = Vec::from_raw_parts(ptr, cap, cap);
-            UnsafeCell::new(vec.into_boxed_slice())
-        };
-        StringArena{arena, idx: Cell::new(0)}
-    }
-
-    /// Returns bytes written of current arena.
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.idx.get()
-    }
+mod arena;
+#[cfg(target_pointer_width = "64")]
+mod arrow;
+mod backing;
+mod budget;
+mod builder;
+mod chained;
+mod compat;
+#[cfg(feature = "std")]
+mod crc;
+#[cfg(feature = "csv")]
+mod csv;
+mod escape;
+mod freeable;
+mod frontcoded;
+mod frozen;
+mod growable;
+mod indexed;
+mod inline;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "std")]
+mod interner;
+#[cfg(feature = "std")]
+mod io;
+#[cfg(feature = "std")]
+mod local;
+#[cfg(feature = "lz4")]
+mod lz4;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+#[cfg(feature = "std")]
+mod os;
+mod observer;
+#[cfg(feature = "std")]
+mod persist;
+mod phf;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(all(feature = "mmap", unix))]
+mod reserved;
+mod rope;
+mod scope;
+mod secret;
+mod sharded;
+#[cfg(all(feature = "std", feature = "mmap", unix))]
+mod shm;
+mod sorted;
+mod source;
+mod span;
+mod stats;
+mod strvec;
+mod substring;
+mod sync;
+#[cfg(all(feature = "mmap", unix))]
+mod sys;
+mod transcode;
+mod trie;
+mod writer;
 
-    /// Returns whether the arena is empty.
-    #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
-    }
+#[cfg(feature = "poison")]
+pub use arena::POISON;
+pub use arena::{Mark, StringArena, StringArenaRef};
+#[cfg(target_pointer_width = "64")]
+pub use arrow::ArrowBuffers;
+pub use backing::{BackingAlloc, BufferAlloc, Global};
+pub use budget::MemoryBudget;
+pub use builder::{GrowableArenaBuilder, StringArenaBuilder};
+pub use chained::ChainedStringArena;
+#[cfg(feature = "csv")]
+pub use csv::CsvReader;
+pub use freeable::{FreeId, FreeableStringArena};
+pub use frontcoded::FrontCodedStringArena;
+pub use frozen::{ArenaStr, FrozenArena};
+pub use growable::{GrowableStringArena, GrowthPolicy};
+pub use indexed::{IndexedStringArena, IndexedIter, RemapTable, StrId};
+pub use inline::InlineStringArena;
+#[cfg(feature = "json")]
+pub use json::ArenaJsonValue;
+#[cfg(feature = "std")]
+pub use interner::{CaseFolding, StringInterner};
+#[cfg(feature = "std")]
+pub use local::{local_scope, set_local_capacity, with_arena, DEFAULT_LOCAL_CAPACITY};
+#[cfg(all(feature = "mmap", unix))]
+pub use mmap::{HugePages, Mmap, NumaPolicy, GUARD_PAGE_SIZE, HUGE_PAGE_SIZE};
+pub use observer::Observer;
+#[cfg(feature = "std")]
+pub use persist::FORMAT_VERSION;
+pub use phf::PerfectHashIndex;
+#[cfg(feature = "std")]
+pub use pool::{ArenaPool, PooledArena, ShrinkPolicy};
+#[cfg(all(feature = "mmap", unix))]
+pub use reserved::ReservedStringArena;
+pub use rope::ArenaRope;
+pub use scope::{ScopeGuard, ScopedArena};
+pub use secret::SecretStringArena;
+pub use sharded::{FrozenShards, ShardWriter, ShardedId, ShardedStringArena};
+pub use sorted::SortedStringArena;
+pub use source::{FileId, Location, SourceMap};
+pub use span::Span;
+pub use stats::Stats;
+pub use strvec::ArenaStrVec;
+pub use substring::SubstringIndex;
+pub use sync::{SyncStrId, SyncStringArena};
+pub use trie::TrieInterner;
+#[cfg(feature = "std")]
+pub use writer::ArenaByteWriter;
+pub use writer::ArenaWriter;
 
-    /// Returns capacity of current arena.
-    #[inline]
-    pub fn capacity(&self) -> usize {
-        unsafe { (*self.arena.get()).len() }
-    }
+pub type Result<T> = core::result::Result<T, Error>;
 
-    /// Add a string into current arena.
-    /// Returns the string ref if succeeds.
-    /// The only reason of failure is that input string exceeds remained capacity.
-    /// The additional bytes required to store it is returned if fails. 
-    #[inline]
-    pub fn add<T: AsRef<str>>(&self, s: T) -> Result<&str> {
-        let s = s.as_ref();
-        let len = s.len();
-        let idx = self.len();
-        let new_len = len + idx;
-        if self.capacity() < new_len {
-            return Err(Error::ExceedsCapacity(new_len - self.capacity()))
-        }
-        // SAFETY:
-        // 
-        // The mutable byte slice is guaranteed not to be modified concurrently.
-        unsafe {
-            let arena = &mut *self.arena.get();
-            let bs = &mut arena[idx..new_len];
-            bs.copy_from_slice(s.as_bytes());
-            self.idx.set(new_len);
-            Ok(std::str::from_utf8_unchecked(bs))
-        }
-    }
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(thiserror::Error))]
+pub enum Error {
+    #[cfg_attr(feature = "std", error("exceeds capacity with additional {0} bytes"))]
+    ExceedsCapacity(usize),
+    #[cfg_attr(feature = "std", error("failed to allocate {0} bytes"))]
+    AllocFailed(usize),
+    #[cfg_attr(feature = "std", error("exceeds maximum number of strings"))]
+    TooManyStrings,
+    #[cfg_attr(feature = "std", error("formatting failed"))]
+    FormatFailed,
+    #[cfg_attr(feature = "std", error("interior nul byte found at position {0}"))]
+    InteriorNul(usize),
+    #[cfg_attr(feature = "std", error("invalid utf-8 sequence after {0} valid bytes"))]
+    InvalidUtf8(usize),
+    #[cfg_attr(feature = "std", error("unpaired surrogate found at position {0}"))]
+    InvalidUtf16(usize),
+    #[cfg_attr(feature = "std", error("invalid json at position {0}"))]
+    InvalidJson(usize),
+    #[cfg_attr(feature = "std", error("failed to lock {0} bytes in memory"))]
+    LockFailed(usize),
+    #[cfg_attr(feature = "std", error("exceeds memory budget with additional {0} bytes"))]
+    BudgetExceeded(usize),
+    #[cfg_attr(feature = "std", error("invalid arena file format"))]
+    InvalidFormat,
+    #[cfg_attr(feature = "std", error("unsupported arena file format version {0}"))]
+    UnsupportedVersion(u16),
+    #[cfg_attr(feature = "std", error("checksum mismatch of arena file"))]
+    ChecksumMismatch,
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "std", error("i/o error: {0}"))]
+    Io(std::io::Error),
+}
 
-    /// Renew the arena for future usage.
-    /// This is safe because all string refs associated to this arena
-    /// must be dropped before this method call.
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for Error {
     #[inline]
-    pub fn renew(self) -> Self {
-        self.idx.set(0);
-        self
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self, f)
     }
 }
 
+/// Format a string directly into an arena, returning the string ref.
+///
+/// `arena_write!(arena, "{}-{}", a, b)` is shorthand for
+/// `arena.add_fmt(format_args!("{}-{}", a, b))`.
+#[macro_export]
+macro_rules! arena_write {
+    ($arena:expr, $($arg:tt)*) => {
+        $arena.add_fmt(::core::format_args!($($arg)*))
+    };
+}
 
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_string_arena() {
-        let sa = StringArena::with_capacity(12);
-        assert_eq!(sa.len(), 0);
-        assert!(sa.is_empty());
-        assert_eq!(sa.capacity(), 12);
-        let s1 = sa.add("hello").unwrap();
-        assert_eq!(s1, "hello");
-        let s2 = sa.add("world").unwrap();
-        assert_eq!(s2, "world");
-        assert!(sa.add("rust").is_err());
-        let sa2 = sa.renew();
-        assert!(sa2.is_empty());
-    }
+/// Run the statements, and discard all strings added into the thread-local
+/// arena by them on exit.
+///
+/// `scoped! { ... }` is shorthand for `local_scope(|| { ... })`.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! scoped {
+    ($($body:tt)*) => {
+        $crate::local_scope(|| { $($body)* })
+    };
 }
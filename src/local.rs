@@ -0,0 +1,98 @@
+//! Thread-local default arena, which requires `std`.
+use crate::StringArena;
+use std::cell::{Cell, RefCell};
+
+/// Default capacity of the thread-local arena.
+pub const DEFAULT_LOCAL_CAPACITY: usize = 64 * 1024;
+
+thread_local! {
+    static LOCAL_CAPACITY: Cell<usize> = const { Cell::new(DEFAULT_LOCAL_CAPACITY) };
+    static LOCAL: RefCell<Option<StringArena>> = const { RefCell::new(None) };
+}
+
+/// Set capacity of the thread-local arena of current thread.
+/// The arena is recreated with the new capacity on next use.
+///
+/// # Panics
+///
+/// Panics if called inside [`with_arena`].
+#[inline]
+pub fn set_local_capacity(cap: usize) {
+    LOCAL_CAPACITY.with(|c| c.set(cap));
+    LOCAL.with(|l| l.borrow_mut().take());
+}
+
+/// Run the closure with the thread-local arena of current thread.
+/// The arena is allocated on first use, and strings added are kept
+/// until the enclosing [`local_scope`] exits.
+/// Calls can be nested.
+///
+/// # Panics
+///
+/// Panics if the allocation fails.
+#[inline]
+pub fn with_arena<R>(f: impl FnOnce(&StringArena) -> R) -> R {
+    LOCAL.with(|l| {
+        if l.borrow().is_none() {
+            let cap = LOCAL_CAPACITY.with(Cell::get);
+            *l.borrow_mut() = Some(StringArena::with_capacity(cap));
+        }
+        f(l.borrow().as_ref().unwrap())
+    })
+}
+
+/// Run the closure, and discard all strings added into the thread-local
+/// arena during the closure on exit, even if it panics.
+/// See also [`scoped!`](crate::scoped).
+#[inline]
+pub fn local_scope<R>(f: impl FnOnce() -> R) -> R {
+    // Mark of the arena on entry, or None if it is not allocated yet.
+    struct Guard(Option<crate::Mark>);
+
+    impl Drop for Guard {
+        #[inline]
+        fn drop(&mut self) {
+            LOCAL.with(|l| {
+                // The arena may be borrowed if the scope is inside with_arena(),
+                // then strings are discarded by the outer scope.
+                if let Ok(mut l) = l.try_borrow_mut() {
+                    match (l.as_mut(), self.0) {
+                        (Some(sa), Some(mark)) => sa.truncate_to(mark),
+                        (Some(sa), None) => sa.clear(),
+                        (None, _) => (),
+                    }
+                }
+            })
+        }
+    }
+
+    let _guard = Guard(LOCAL.with(|l| l.borrow().as_ref().map(StringArena::checkpoint)));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_arena() {
+        set_local_capacity(64);
+        let len = || with_arena(|sa| sa.len());
+        crate::scoped! {
+            with_arena(|sa| sa.add("hello").map(|_| ())).unwrap();
+            let s = with_arena(|sa| {
+                let s = sa.add("world").unwrap();
+                with_arena(|sa2| assert_eq!(sa2.len(), 10));
+                s.to_uppercase()
+            });
+            assert_eq!(s, "WORLD");
+            crate::scoped! {
+                with_arena(|sa| sa.add("inner").map(|_| ())).unwrap();
+                assert_eq!(len(), 15);
+            }
+            assert_eq!(len(), 10);
+        }
+        assert_eq!(len(), 0);
+        assert_eq!(with_arena(|sa| sa.capacity()), 64);
+    }
+}
@@ -0,0 +1,176 @@
+//! LZ4 block compression of saved arenas, which requires feature `lz4`.
+//!
+//! A block is a series of sequences, each of a token, literal bytes, and
+//! a back reference of 2-byte offset and length, except the last sequence
+//! which only has literals. The upper and lower 4 bits of the token hold
+//! the literal length and the match length minus 4, extended by following
+//! bytes summed until one other than 255 if all bits are set.
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Minimum length of a match.
+const MIN_MATCH: usize = 4;
+
+/// Bytes at the end of a block which are always literals.
+const LAST_LITERALS: usize = 5;
+
+/// Matches must start no later than this many bytes before the end.
+const MF_LIMIT: usize = 12;
+
+/// Maximum offset of a back reference.
+const MAX_OFFSET: usize = u16::MAX as usize;
+
+/// Bits of the hash of 4-byte sequences to find matches.
+const HASH_BITS: u32 = 14;
+
+/// Compress bytes into an LZ4 block.
+pub(crate) fn compress(src: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(src.len() / 2 + 16);
+    // Position of the last sequence of each hash.
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let (mut anchor, mut i) = (0, 0);
+    while i + MF_LIMIT <= src.len() {
+        let seq = u32::from_le_bytes(src[i..i + 4].try_into().unwrap());
+        let h = (seq.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+        let cand = core::mem::replace(&mut table[h], i);
+        if cand == usize::MAX || i - cand > MAX_OFFSET || src[cand..cand + 4] != src[i..i + 4] {
+            i += 1;
+            continue;
+        }
+        let limit = src.len() - LAST_LITERALS - i;
+        let mut len = MIN_MATCH;
+        while len < limit && src[cand + len] == src[i + len] {
+            len += 1;
+        }
+        emit(&mut out, &src[anchor..i], Some((i - cand, len)));
+        i += len;
+        anchor = i;
+    }
+    emit(&mut out, &src[anchor..], None);
+    out
+}
+
+/// Write a sequence of literals and an optional match of given offset and
+/// length.
+fn emit(out: &mut Vec<u8>, lits: &[u8], m: Option<(usize, usize)>) {
+    let ml = m.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push((lits.len().min(15) as u8) << 4 | ml.min(15) as u8);
+    if lits.len() >= 15 {
+        write_len(out, lits.len() - 15);
+    }
+    out.extend_from_slice(lits);
+    if let Some((offset, _)) = m {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if ml >= 15 {
+            write_len(out, ml - 15);
+        }
+    }
+}
+
+#[inline]
+fn write_len(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 255 {
+        out.push(255);
+        n -= 255;
+    }
+    out.push(n as u8);
+}
+
+/// Decompress an LZ4 block into the buffer, which must be filled exactly.
+/// Returns None if the block is malformed.
+pub(crate) fn decompress(src: &[u8], dst: &mut [u8]) -> Option<()> {
+    let (mut i, mut o) = (0usize, 0usize);
+    loop {
+        let token = *src.get(i)?;
+        i += 1;
+        let mut lit = (token >> 4) as usize;
+        if lit == 15 {
+            lit = lit.checked_add(read_len(src, &mut i)?)?;
+        }
+        let end = i.checked_add(lit)?;
+        dst.get_mut(o..o.checked_add(lit)?)?.copy_from_slice(src.get(i..end)?);
+        (i, o) = (end, o + lit);
+        if i == src.len() {
+            return (o == dst.len()).then_some(());
+        }
+        let offset = u16::from_le_bytes(src.get(i..i + 2)?.try_into().unwrap()) as usize;
+        i += 2;
+        if offset == 0 || offset > o {
+            return None;
+        }
+        let mut len = (token & 15) as usize + MIN_MATCH;
+        if token & 15 == 15 {
+            len = len.checked_add(read_len(src, &mut i)?)?;
+        }
+        if len > dst.len() - o {
+            return None;
+        }
+        // The match may overlap the bytes it produces, so copy one by one.
+        for k in o..o + len {
+            dst[k] = dst[k - offset];
+        }
+        o += len;
+    }
+}
+
+#[inline]
+fn read_len(src: &[u8], i: &mut usize) -> Option<usize> {
+    let mut n = 0usize;
+    loop {
+        let b = *src.get(*i)?;
+        *i += 1;
+        n = n.checked_add(b as usize)?;
+        if b != 255 {
+            return Some(n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(src: &[u8]) -> Vec<u8> {
+        let block = compress(src);
+        let mut dst = vec![0u8; src.len()];
+        decompress(&block, &mut dst).unwrap();
+        assert_eq!(dst, src);
+        block
+    }
+
+    #[test]
+    fn test_lz4() {
+        assert_eq!(round_trip(b""), [0]);
+        assert_eq!(round_trip(b"hello"), b"\x50hello");
+        let text = "the quick brown fox jumps over the lazy dog. ".repeat(100);
+        assert!(round_trip(text.as_bytes()).len() < text.len() / 10);
+        assert!(round_trip(&[7u8; 100_000]).len() < 500);
+        let mut x = 1u32;
+        let noise: Vec<u8> = (0..70_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                (x % 7) as u8
+            })
+            .collect();
+        round_trip(&noise);
+        // A match overlapping its output, followed by no literals.
+        let mut dst = [0u8; 20];
+        decompress(b"\x1fa\x01\x00\x00\x00", &mut dst).unwrap();
+        assert_eq!(dst, [b'a'; 20]);
+    }
+
+    #[test]
+    fn test_lz4_malformed() {
+        let block = compress("abcabcabcabcabcabcabcabc".as_bytes());
+        let mut dst = [0u8; 24];
+        for n in 0..block.len() {
+            assert!(decompress(&block[..n], &mut dst).is_none());
+        }
+        assert!(decompress(&block, &mut [0u8; 23]).is_none());
+        assert!(decompress(&block, &mut [0u8; 25]).is_none());
+        assert!(decompress(b"\x10a\x02\x00", &mut dst).is_none());
+        assert!(decompress(b"\xf0\xff\xff", &mut dst).is_none());
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{sys, BackingAlloc, Result, StringArena};
+use core::alloc::Layout;
+use core::ptr::{self, NonNull};
+
+/// Size of huge pages requested by [`HugePages::Explicit`].
+pub const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// Size of the guard region placed after the buffer, which is a multiple
+/// of page size on all supported platforms.
+pub const GUARD_PAGE_SIZE: usize = 64 * 1024;
+
+/// Granularity of releasing memory on clear, which is a multiple of page
+/// size on all supported platforms.
+const PURGE_UNIT: usize = 64 * 1024;
+
+/// HugePages controls whether the mapping is backed by huge pages.
+///
+/// Huge pages are only supported on Linux, and ignored on other platforms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HugePages {
+    /// Use normal pages.
+    #[default]
+    None,
+    /// Advise kernel to back the mapping with transparent huge pages.
+    /// It silently falls back to normal pages if not available.
+    Transparent,
+    /// Map explicit huge pages from the pool reserved by kernel.
+    /// Mapping length is rounded up to [`HUGE_PAGE_SIZE`], and the
+    /// mapping fails if the pool is exhausted.
+    Explicit,
+}
+
+/// NumaPolicy controls which NUMA nodes back the mapping.
+///
+/// Policies are only supported on Linux, and ignored on other platforms.
+/// They are best-effort, e.g. a container may forbid changing policy,
+/// and failure is ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumaPolicy {
+    /// Use the default policy of current thread, which usually allocates
+    /// pages on the node of the thread first touching them.
+    #[default]
+    Default,
+    /// Bind pages to given node, which must be one of the first 64 nodes.
+    Bind(usize),
+    /// Interleave pages over nodes in given bit mask of the first 64 nodes.
+    Interleave(u64),
+}
+
+/// Mmap is the backing allocator which maps anonymous memory from OS.
+///
+/// Pages are committed lazily on first write, and returned to OS as soon
+/// as the arena is dropped, which suits arenas with very large capacity.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mmap {
+    huge_pages: HugePages,
+    guard_page: bool,
+    numa: NumaPolicy,
+    release_on_clear: bool,
+}
+
+impl Mmap {
+    /// Create a new mmap allocator with normal pages.
+    #[inline]
+    pub const fn new() -> Self {
+        Mmap {
+            huge_pages: HugePages::None,
+            guard_page: false,
+            numa: NumaPolicy::Default,
+            release_on_clear: false,
+        }
+    }
+
+    /// Set huge page mode of the mapping.
+    #[inline]
+    pub const fn huge_pages(mut self, huge_pages: HugePages) -> Self {
+        self.huge_pages = huge_pages;
+        self
+    }
+
+    /// Set whether an inaccessible guard region is placed right after
+    /// the buffer, so overruns crash deterministically. It is meant for
+    /// debugging and fuzzing, and ignored with explicit huge pages.
+    #[inline]
+    pub const fn guard_page(mut self, guard_page: bool) -> Self {
+        self.guard_page = guard_page;
+        self
+    }
+
+    /// Set the NUMA policy of the mapping.
+    #[inline]
+    pub const fn numa(mut self, numa: NumaPolicy) -> Self {
+        self.numa = numa;
+        self
+    }
+
+    /// Set whether memory is returned to OS when the arena is cleared,
+    /// so resident memory drops while the mapping is kept. It only
+    /// applies to whole 64 KiB units of the cleared region.
+    #[inline]
+    pub const fn release_on_clear(mut self, release_on_clear: bool) -> Self {
+        self.release_on_clear = release_on_clear;
+        self
+    }
+
+    #[inline]
+    fn explicit_huge_pages(&self) -> bool {
+        let linux = cfg!(any(target_os = "linux", target_os = "android"));
+        linux && self.huge_pages == HugePages::Explicit
+    }
+
+    /// Returns length of the mapping, and offset of the buffer in it.
+    #[inline]
+    fn mapping(&self, layout: Layout) -> (usize, usize) {
+        let size = layout.size();
+        if self.explicit_huge_pages() {
+            (size.next_multiple_of(HUGE_PAGE_SIZE), 0)
+        } else if self.guard_page {
+            // The buffer ends at the guard region, as far as alignment allows.
+            let data_len = size.next_multiple_of(GUARD_PAGE_SIZE);
+            let offset = (data_len - size) & !(layout.align() - 1);
+            (data_len + GUARD_PAGE_SIZE, offset)
+        } else {
+            (size, 0)
+        }
+    }
+}
+
+// SAFETY:
+//
+// Each mapping is private and page-aligned.
+unsafe impl BackingAlloc for Mmap {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let (len, offset) = self.mapping(layout);
+        #[allow(unused_mut)]
+        let mut flags = sys::MAP_PRIVATE | sys::MAP_ANONYMOUS;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if self.huge_pages == HugePages::Explicit {
+            flags |= sys::MAP_HUGETLB;
+        }
+        // SAFETY:
+        //
+        // Anonymous mapping does not touch existing memory.
+        let ptr = unsafe {
+            sys::mmap(
+                ptr::null_mut(),
+                len,
+                sys::PROT_READ | sys::PROT_WRITE,
+                flags,
+                -1,
+                0,
+            )
+        };
+        if ptr == sys::MAP_FAILED {
+            return None;
+        }
+        let ptr = NonNull::new(ptr as *mut u8)?;
+        if ptr.as_ptr().align_offset(layout.align()) != 0 {
+            // SAFETY:
+            //
+            // The mapping is just created above.
+            unsafe { sys::munmap(ptr.as_ptr() as *mut _, len) };
+            return None;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if self.huge_pages == HugePages::Transparent {
+            // The advice is best-effort, failure is ignored.
+            // SAFETY:
+            //
+            // The range is mapped above.
+            unsafe { sys::madvise(ptr.as_ptr() as *mut _, len, sys::MADV_HUGEPAGE) };
+        }
+        #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            let policy = match self.numa {
+                NumaPolicy::Default => None,
+                NumaPolicy::Bind(node) if node < 64 => Some((sys::MPOL_BIND, 1u64 << node)),
+                NumaPolicy::Bind(_) => None,
+                NumaPolicy::Interleave(mask) => Some((sys::MPOL_INTERLEAVE, mask)),
+            };
+            if let Some((mode, mask)) = policy {
+                // The policy is best-effort, failure is ignored.
+                // SAFETY:
+                //
+                // The range is mapped above, and pages are not touched yet.
+                unsafe { sys::mbind(ptr.as_ptr() as *mut _, len, mode, mask) };
+            }
+        }
+        if self.guard_page && !self.explicit_huge_pages() {
+            let guard = len - GUARD_PAGE_SIZE;
+            // SAFETY:
+            //
+            // The guard region is at the end of the mapping created above.
+            unsafe {
+                let addr = ptr.as_ptr().add(guard) as *mut _;
+                if sys::mprotect(addr, GUARD_PAGE_SIZE, sys::PROT_NONE) != 0 {
+                    sys::munmap(ptr.as_ptr() as *mut _, len);
+                    return None;
+                }
+                return Some(NonNull::new_unchecked(ptr.as_ptr().add(offset)));
+            }
+        }
+        Some(ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let (len, offset) = self.mapping(layout);
+        sys::munmap(ptr.as_ptr().sub(offset) as *mut _, len);
+    }
+
+    #[inline]
+    unsafe fn purge(&self, ptr: NonNull<u8>, len: usize) {
+        if !self.release_on_clear {
+            return;
+        }
+        let start = ptr.as_ptr().align_offset(PURGE_UNIT);
+        let end = (ptr.as_ptr() as usize + len) / PURGE_UNIT * PURGE_UNIT - ptr.as_ptr() as usize;
+        if start < end {
+            // The advice is best-effort, failure is ignored.
+            sys::madvise(ptr.as_ptr().add(start) as *mut _, end - start, sys::MADV_DONTNEED);
+        }
+    }
+}
+
+impl StringArena<Mmap> {
+    /// Create a new string arena with given capacity, backed by anonymous
+    /// memory mapping.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mapping fails.
+    #[inline]
+    pub fn with_capacity_mmap(cap: usize) -> Self {
+        Self::with_capacity_in(cap, Mmap::new())
+    }
+
+    /// Try to create a new string arena with given capacity, backed by
+    /// anonymous memory mapping.
+    /// Returns error if the mapping fails.
+    #[inline]
+    pub fn try_with_capacity_mmap(cap: usize) -> Result<Self> {
+        Self::try_with_capacity_in(cap, Mmap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mmap_string_arena() {
+        // Reserve 1 GiB, only touched pages are committed.
+        let sa = StringArena::with_capacity_mmap(1 << 30);
+        assert_eq!(sa.capacity(), 1 << 30);
+        let s1 = sa.add("hello").unwrap();
+        let s2 = sa.add("world").unwrap();
+        assert_eq!(s1, "hello");
+        assert_eq!(s2, "world");
+        assert!(StringArena::try_with_capacity_mmap(usize::MAX / 2).is_err());
+    }
+
+    #[test]
+    fn test_mmap_guard_page() {
+        let sa = StringArena::with_capacity_in(100, Mmap::new().guard_page(true));
+        assert_eq!(sa.add("hello").unwrap(), "hello");
+        // The buffer ends at a page boundary, where the guard region starts.
+        let end = sa.add("!").unwrap().as_ptr() as usize + 95;
+        assert_eq!(end % 4096, 0);
+    }
+
+    #[test]
+    fn test_mmap_numa() {
+        for numa in [NumaPolicy::Bind(0), NumaPolicy::Interleave(1), NumaPolicy::Bind(64)] {
+            let sa = StringArena::with_capacity_in(1 << 20, Mmap::new().numa(numa));
+            assert_eq!(sa.add("hello").unwrap(), "hello");
+        }
+    }
+
+    #[test]
+    fn test_mmap_release_on_clear() {
+        let alloc = Mmap::new().release_on_clear(true);
+        check_release_on_clear(StringArena::with_capacity_in(PURGE_UNIT * 4, alloc));
+        check_release_on_clear(StringArena::with_capacity_in(PURGE_UNIT * 4, &alloc));
+    }
+
+    fn check_release_on_clear<A: BackingAlloc>(mut sa: StringArena<A>) {
+        let s = "x".repeat(PURGE_UNIT * 3);
+        sa.add(&s).unwrap();
+        sa.clear();
+        assert!(sa.is_empty());
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            // Released pages read as zero on Linux.
+            let bs = unsafe { core::slice::from_raw_parts(sa.as_mut_ptr(), PURGE_UNIT * 2) };
+            assert!(bs[PURGE_UNIT..].iter().all(|&b| b == 0));
+        }
+        assert_eq!(sa.add(&s).unwrap(), s);
+    }
+
+    #[test]
+    fn test_mmap_huge_pages() {
+        let alloc = Mmap::new().huge_pages(HugePages::Transparent);
+        let sa = StringArena::with_capacity_in(4 * HUGE_PAGE_SIZE, alloc);
+        assert_eq!(sa.add("hello").unwrap(), "hello");
+        // Explicit huge pages depend on kernel reservation, so only
+        // check the arena works if the mapping succeeds.
+        let alloc = Mmap::new().huge_pages(HugePages::Explicit);
+        if let Ok(sa) = StringArena::try_with_capacity_in(100, alloc) {
+            assert_eq!(sa.add("world").unwrap(), "world");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+/// Observer is notified of events of [`StringArena`](crate::StringArena),
+/// e.g. to feed metrics counters, tracing events or custom accounting.
+///
+/// All methods do nothing by default, so implementations only override
+/// events of interest. They are invoked synchronously on the thread
+/// using the arena, and should be cheap.
+pub trait Observer: Send + Sync {
+    /// Invoked after an arena of given capacity is built with the observer
+    /// by [`StringArenaBuilder`](crate::StringArenaBuilder), including each
+    /// chunk allocated by a growable arena.
+    #[inline]
+    fn on_create(&self, _cap: usize) {}
+
+    /// Invoked after a string of given length is added.
+    #[inline]
+    fn on_add(&self, _len: usize) {}
+
+    /// Invoked after the arena is cleared, with bytes discarded.
+    #[inline]
+    fn on_clear(&self, _len: usize) {}
+
+    /// Invoked after an addition of given length fails because of
+    /// exceeding capacity.
+    #[inline]
+    fn on_exceeds_capacity(&self, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GrowthPolicy, StringArena};
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Counter {
+        created: AtomicUsize,
+        added: AtomicUsize,
+        cleared: AtomicUsize,
+        rejected: AtomicUsize,
+    }
+
+    impl Observer for Counter {
+        fn on_create(&self, cap: usize) {
+            self.created.fetch_add(cap, Ordering::Relaxed);
+        }
+
+        fn on_add(&self, len: usize) {
+            self.added.fetch_add(len, Ordering::Relaxed);
+        }
+
+        fn on_clear(&self, len: usize) {
+            self.cleared.fetch_add(len, Ordering::Relaxed);
+        }
+
+        fn on_exceeds_capacity(&self, len: usize) {
+            self.rejected.fetch_add(len, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let counter = Arc::new(Counter::default());
+        let mut sa = StringArena::with_capacity(16);
+        sa.set_observer(Some(counter.clone()));
+        sa.add("hello").unwrap();
+        sa.add_fmt(format_args!("{}-{}", 1, 2)).unwrap();
+        assert!(sa.add("a long string").is_err());
+        sa.clear();
+        assert_eq!(counter.added.load(Ordering::Relaxed), 8);
+        assert_eq!(counter.cleared.load(Ordering::Relaxed), 8);
+        assert_eq!(counter.rejected.load(Ordering::Relaxed), 13);
+        sa.set_observer(None);
+        sa.add("hello").unwrap();
+        assert_eq!(counter.added.load(Ordering::Relaxed), 8);
+        assert_eq!(counter.created.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_observer_on_create() {
+        let counter = Arc::new(Counter::default());
+        let bld = StringArena::builder().capacity(8).observer(counter.clone());
+        bld.clone().build();
+        assert_eq!(counter.created.load(Ordering::Relaxed), 8);
+        let ga = bld.growth(GrowthPolicy::Doubling).build();
+        ga.add("hello");
+        ga.add("world");
+        ga.add("again");
+        assert_eq!((ga.chunks(), counter.created.load(Ordering::Relaxed)), (2, 8 + 8 + 16));
+    }
+}
@@ -0,0 +1,44 @@
+//! Storage of platform strings, which requires `std`.
+use crate::{BackingAlloc, Result, StringArena};
+use std::ffi::OsStr;
+use std::path::Path;
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Add an OS string into current arena.
+    /// Returns the OS string ref if succeeds.
+    /// The platform-specific representation is kept as is, so no
+    /// information is lost on any platform.
+    #[inline]
+    pub fn add_os_str<T: AsRef<OsStr>>(&self, s: T) -> Result<&OsStr> {
+        let bs = self.add_bytes(s.as_ref().as_encoded_bytes())?;
+        // SAFETY:
+        //
+        // The bytes are copied from encoded bytes of an OS string.
+        unsafe { Ok(OsStr::from_encoded_bytes_unchecked(bs)) }
+    }
+
+    /// Add a path into current arena.
+    /// Returns the path ref if succeeds.
+    #[inline]
+    pub fn add_path<T: AsRef<Path>>(&self, p: T) -> Result<&Path> {
+        self.add_os_str(p.as_ref().as_os_str()).map(Path::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StringArena;
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_add_os_str_and_path() {
+        let sa = StringArena::with_capacity(32);
+        let s = sa.add_os_str(OsStr::new("hello")).unwrap();
+        assert_eq!(s, "hello");
+        let p = sa.add_path(PathBuf::from("src").join("lib.rs")).unwrap();
+        assert_eq!(p, Path::new("src").join("lib.rs"));
+        assert_eq!(p.file_name().unwrap(), "lib.rs");
+        assert!(sa.add_path("a/very/long/path/exceeding/capacity").is_err());
+    }
+}
@@ -0,0 +1,453 @@
+//! Persistence of indexed arenas to files, which requires `std`.
+//!
+//! A saved arena has a header of 32 bytes, then offsets of strings, then
+//! bytes of strings, then a CRC-32 of the offsets and bytes if flagged.
+//! Bytes of strings are an LZ4 block if flagged, which is saved and opened
+//! with feature `lz4`. All integers are little-endian.
+//!
+//! | Bytes  | Field                                       |
+//! |--------|---------------------------------------------|
+//! | 0..8   | magic `AOSAIDX\0`                           |
+//! | 8..10  | format version, currently 1                 |
+//! | 10..11 | flags, bit 0 set if the checksum follows,   |
+//! |        | bit 1 set if bytes are compressed           |
+//! | 11..12 | width of each offset in bytes, 4 or 8       |
+//! | 12..16 | generation of the arena                     |
+//! | 16..24 | number of strings                           |
+//! | 24..32 | total bytes of strings, before compression  |
+use crate::crc::Crc32;
+use crate::{Error, IndexedStringArena, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[cfg(all(feature = "mmap", unix))]
+use crate::{sys, FrozenArena};
+#[cfg(all(feature = "lz4", feature = "mmap", unix))]
+use crate::StringArena;
+#[cfg(all(feature = "mmap", unix))]
+use alloc::vec::Vec;
+#[cfg(all(feature = "mmap", unix))]
+use core::ptr::NonNull;
+
+/// Magic bytes at the start of a saved arena.
+const MAGIC: [u8; 8] = *b"AOSAIDX\0";
+
+/// Version of the format written.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Length of the header.
+const HEADER_LEN: usize = 32;
+
+/// Flag of the checksum following bytes of strings.
+const FLAG_CHECKSUM: u8 = 1;
+
+/// Flag of bytes of strings compressed as an LZ4 block.
+const FLAG_COMPRESSED: u8 = 2;
+
+/// Flags which can be opened.
+#[cfg(all(feature = "mmap", unix))]
+const KNOWN_FLAGS: u8 = if cfg!(feature = "lz4") {
+    FLAG_CHECKSUM | FLAG_COMPRESSED
+} else {
+    FLAG_CHECKSUM
+};
+
+/// Length of the checksum.
+#[cfg(all(feature = "mmap", unix))]
+const CHECKSUM_LEN: usize = 4;
+
+impl IndexedStringArena {
+    /// Save the arena to a file at given path, which can be opened by
+    /// `FrozenArena::open_mmap` with feature `mmap`.
+    ///
+    /// Offsets are written in 4 bytes if all strings fit, otherwise in
+    /// 8 bytes. A checksum of offsets and bytes is appended, which is
+    /// verified on opening.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.write_file(File::create(path).map_err(Error::Io)?, None)
+    }
+
+    /// Save the arena to a file like [`IndexedStringArena::save_to`], but
+    /// with bytes of strings compressed as an LZ4 block.
+    ///
+    /// Opening the file decompresses all bytes into memory, so it suits
+    /// files shipped for distribution rather than large snapshots.
+    #[cfg(feature = "lz4")]
+    pub fn save_to_compressed<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let block = crate::lz4::compress(self.values());
+        self.write_file(File::create(path).map_err(Error::Io)?, Some(&block))
+    }
+
+    /// Write the arena to the file, with the compressed block in place of
+    /// bytes of strings if given.
+    #[inline]
+    pub(crate) fn write_file(&self, file: File, block: Option<&[u8]>) -> Result<()> {
+        let mut w = BufWriter::new(file);
+        let width: u8 = if u32::try_from(self.len()).is_ok() { 4 } else { 8 };
+        let mut header = [0u8; HEADER_LEN];
+        header[..8].copy_from_slice(&MAGIC);
+        header[8..10].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header[10] = FLAG_CHECKSUM | if block.is_some() { FLAG_COMPRESSED } else { 0 };
+        header[11] = width;
+        header[12..16].copy_from_slice(&self.generation().to_le_bytes());
+        header[16..24].copy_from_slice(&(self.len_strings() as u64).to_le_bytes());
+        header[24..].copy_from_slice(&(self.len() as u64).to_le_bytes());
+        w.write_all(&header).map_err(Error::Io)?;
+        let mut crc = Crc32::new();
+        for &o in self.offsets_ref() {
+            let (bs, n) = ((o as u64).to_le_bytes(), width as usize);
+            crc.update(&bs[..n]);
+            w.write_all(&bs[..n]).map_err(Error::Io)?;
+        }
+        let values = block.unwrap_or(self.values());
+        crc.update(values);
+        w.write_all(values).map_err(Error::Io)?;
+        w.write_all(&crc.finish().to_le_bytes()).map_err(Error::Io)?;
+        w.flush().map_err(Error::Io)
+    }
+}
+
+/// Mapping is a read-only mapping of a whole file, unmapped on drop.
+#[cfg(all(feature = "mmap", unix))]
+pub(crate) struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl Mapping {
+    fn open(file: &File, len: usize) -> Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        // SAFETY:
+        //
+        // The mapping is private and read-only, and does not touch
+        // existing memory.
+        let ptr = unsafe {
+            sys::mmap(
+                core::ptr::null_mut(),
+                len,
+                sys::PROT_READ,
+                sys::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == sys::MAP_FAILED {
+            return Err(Error::Io(std::io::Error::last_os_error()));
+        }
+        let ptr = NonNull::new(ptr as *mut u8).ok_or(Error::InvalidFormat)?;
+        Ok(Mapping { ptr, len })
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        // SAFETY:
+        //
+        // The whole range is mapped readable.
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl Drop for Mapping {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY:
+        //
+        // The range is mapped in open().
+        unsafe { sys::munmap(self.ptr.as_ptr() as *mut _, self.len) };
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl FrozenArena {
+    /// Open a file saved by [`IndexedStringArena::save_to`] as a snapshot,
+    /// by mapping it read-only into memory, so strings are served without
+    /// copying. Handles returned by the saved arena resolve to the same
+    /// strings.
+    ///
+    /// Offsets are used in place if their width and byte order match the
+    /// platform, otherwise they are decoded. The file is validated once on
+    /// opening, and [`Error::ChecksumMismatch`] is returned if its checksum
+    /// does not match.
+    ///
+    /// A file saved by `IndexedStringArena::save_to_compressed` is opened
+    /// with feature `lz4`, by decompressing bytes of strings into memory.
+    /// Its strings and offsets are then always validated.
+    ///
+    /// # Safety
+    ///
+    /// The file is validated only once on opening, so it must not be
+    /// truncated or written while the snapshot is alive, by this or any
+    /// other process. Otherwise reading strings may fault with a bus error,
+    /// or observe bytes that are not valid UTF-8.
+    pub unsafe fn open_mmap<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_file(&File::open(path).map_err(Error::Io)?, true)
+    }
+
+    /// Open a file like [`FrozenArena::open_mmap`], but skip verifying the
+    /// checksum, UTF-8 of strings and their offsets, which read the whole
+    /// file, for trusted sources. Only the header is validated, so opening
+    /// takes constant time besides decoding offsets.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`FrozenArena::open_mmap`]. Besides, the file must be saved
+    /// by [`IndexedStringArena::save_to`] without corruption, so offsets
+    /// are non-decreasing and at char boundaries of valid UTF-8 bytes.
+    pub unsafe fn open_mmap_trusted<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_file(&File::open(path).map_err(Error::Io)?, false)
+    }
+
+    /// Open a saved arena from the file by mapping it.
+    /// Checksum, UTF-8 and offsets are validated only if verify is set.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or written while the snapshot is
+    /// alive. If verify is not set, offsets in the file must be
+    /// non-decreasing and at char boundaries of valid UTF-8 bytes.
+    pub(crate) unsafe fn open_file(file: &File, verify: bool) -> Result<Self> {
+        let len = file.metadata().map_err(Error::Io)?.len();
+        let len = usize::try_from(len).map_err(|_| Error::InvalidFormat)?;
+        if len < HEADER_LEN {
+            return Err(Error::InvalidFormat);
+        }
+        let map = Mapping::open(file, len)?;
+        let bs = map.bytes();
+        if bs[..8] != MAGIC {
+            return Err(Error::InvalidFormat);
+        }
+        let version = u16::from_le_bytes([bs[8], bs[9]]);
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let (flags, width) = (bs[10], bs[11] as usize);
+        if flags & !KNOWN_FLAGS != 0 || (width != 4 && width != 8) {
+            return Err(Error::InvalidFormat);
+        }
+        let trailer = if flags & FLAG_CHECKSUM != 0 { CHECKSUM_LEN } else { 0 };
+        let field = |i: usize| u64::from_le_bytes(bs[i..i + 8].try_into().unwrap());
+        let generation = u32::from_le_bytes(bs[12..16].try_into().unwrap());
+        let strings = usize::try_from(field(16)).map_err(|_| Error::InvalidFormat)?;
+        let values = usize::try_from(field(24)).map_err(|_| Error::InvalidFormat)?;
+        let start = strings
+            .checked_add(1)
+            .and_then(|n| n.checked_mul(width))
+            .and_then(|n| n.checked_add(HEADER_LEN))
+            .ok_or(Error::InvalidFormat)?;
+        let end = len - trailer;
+        let compressed = flags & FLAG_COMPRESSED != 0;
+        // A byte of an LZ4 block decompresses into at most 255 bytes.
+        let valid = if compressed {
+            end >= start && values <= (end - start).saturating_mul(255)
+        } else {
+            start.checked_add(values) == Some(end)
+        };
+        if !valid {
+            return Err(Error::InvalidFormat);
+        }
+        if verify && trailer != 0 {
+            let mut crc = Crc32::new();
+            crc.update(&bs[HEADER_LEN..end]);
+            if crc.finish().to_le_bytes() != bs[end..] {
+                return Err(Error::ChecksumMismatch);
+            }
+        }
+        let raw = &bs[HEADER_LEN..start];
+        let decoded: Vec<usize> = if cfg!(target_endian = "little") && width == size_of::<usize>() {
+            Vec::new()
+        } else if width == 4 {
+            raw.chunks_exact(4)
+                .map(|c| u32::from_le_bytes(c.try_into().unwrap()) as usize)
+                .collect()
+        } else {
+            raw.chunks_exact(8)
+                .map(|c| usize::try_from(u64::from_le_bytes(c.try_into().unwrap())))
+                .collect::<core::result::Result<_, _>>()
+                .map_err(|_| Error::InvalidFormat)?
+        };
+        let offsets = if decoded.is_empty() {
+            // SAFETY:
+            //
+            // The mapping is page-aligned, and offsets start at a boundary
+            // of their width after the header.
+            unsafe { core::slice::from_raw_parts(raw.as_ptr() as *const usize, strings + 1) }
+        } else {
+            &decoded[..]
+        };
+        if offsets[0] != 0 || offsets[strings] != values {
+            return Err(Error::InvalidFormat);
+        }
+        #[cfg(feature = "lz4")]
+        if compressed {
+            let mut buf = alloc::vec![0u8; values];
+            crate::lz4::decompress(&bs[start..end], &mut buf).ok_or(Error::InvalidFormat)?;
+            let sa = StringArena::with_capacity(values);
+            sa.add(check_strings(&buf, offsets)?)?;
+            let offsets = if decoded.is_empty() { offsets.to_vec() } else { decoded };
+            // SAFETY:
+            //
+            // Offsets and bytes are validated above, and bytes are copied
+            // at the start of the arena.
+            return Ok(unsafe { FrozenArena::from_heap(sa, offsets, generation) });
+        }
+        if verify {
+            check_strings(&bs[start..end], offsets)?;
+        }
+        let offsets = NonNull::from(offsets).cast();
+        // SAFETY:
+        //
+        // The bytes start within the mapping.
+        let ptr = unsafe { NonNull::new_unchecked(map.ptr.as_ptr().add(start)) };
+        // SAFETY:
+        //
+        // Offsets and bytes are validated above, or guaranteed by caller,
+        // and the decoded offsets are moved without reallocation.
+        Ok(unsafe { FrozenArena::from_mapping(map, decoded, offsets, strings, ptr, generation) })
+    }
+}
+
+/// Validate that bytes are UTF-8 and offsets are ascending char
+/// boundaries of them.
+#[cfg(all(feature = "mmap", unix))]
+fn check_strings<'a>(bs: &'a [u8], offsets: &[usize]) -> Result<&'a str> {
+    let s = core::str::from_utf8(bs).map_err(|e| Error::InvalidUtf8(e.valid_up_to()))?;
+    let valid = offsets.windows(2).all(|w| w[0] <= w[1])
+        && offsets.iter().all(|&o| s.is_char_boundary(o));
+    if !valid {
+        return Err(Error::InvalidFormat);
+    }
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_to() {
+        let path = std::env::temp_dir().join(format!("aosa-save-{}", std::process::id()));
+        let ia = IndexedStringArena::with_capacity(16);
+        ia.add("hello").unwrap();
+        ia.add("world").unwrap();
+        ia.save_to(&path).unwrap();
+        let bs = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(bs.len(), HEADER_LEN + 12 + 10 + 4);
+        assert_eq!(bs[..8], MAGIC);
+        assert_eq!(bs[8..12], [1, 0, FLAG_CHECKSUM, 4]);
+        assert_eq!(bs[HEADER_LEN..HEADER_LEN + 12], [0, 0, 0, 0, 5, 0, 0, 0, 10, 0, 0, 0]);
+        let mut crc = Crc32::new();
+        crc.update(&bs[HEADER_LEN..bs.len() - 4]);
+        assert_eq!(bs[bs.len() - 14..bs.len() - 4], *b"helloworld");
+        assert_eq!(bs[bs.len() - 4..], crc.finish().to_le_bytes());
+    }
+
+    #[cfg(all(feature = "mmap", unix))]
+    #[test]
+    fn test_save_and_open_mmap() {
+        let path = std::env::temp_dir().join(format!("aosa-persist-{}", std::process::id()));
+        let mut ia = IndexedStringArena::with_capacity(64);
+        ia.add("stale").unwrap();
+        ia.clear();
+        let id1 = ia.add_id("hello").unwrap();
+        let id2 = ia.add_id("").unwrap();
+        let id3 = ia.add_id("世界").unwrap();
+        ia.save_to(&path).unwrap();
+        // SAFETY:
+        //
+        // The file is only written by this test, after snapshots of it are
+        // dropped.
+        unsafe {
+            let fa = FrozenArena::open_mmap(&path).unwrap();
+            assert_eq!(fa.len(), 11);
+            assert_eq!(fa.resolve(id1), "hello");
+            assert_eq!(fa.resolve(id2), "");
+            assert_eq!(&*fa.get(id3), "世界");
+            assert_eq!(fa.iter().collect::<Vec<_>>(), vec!["hello", "", "世界"]);
+            drop(fa);
+            let mut bs = std::fs::read(&path).unwrap();
+            // Offsets of 8 bytes without checksum, which are used in place on
+            // 64-bit platforms.
+            let mut wide = bs[..HEADER_LEN].to_vec();
+            wide[10] = 0;
+            wide[11] = 8;
+            for c in bs[HEADER_LEN..HEADER_LEN + 16].chunks(4) {
+                let o = u32::from_le_bytes(c.try_into().unwrap());
+                wide.extend_from_slice(&u64::from(o).to_le_bytes());
+            }
+            wide.extend_from_slice(&bs[HEADER_LEN + 16..bs.len() - 4]);
+            std::fs::write(&path, &wide).unwrap();
+            let fa = FrozenArena::open_mmap(&path).unwrap();
+            assert_eq!(fa.resolve(id3), "世界");
+            drop(fa);
+            // Corrupted bytes are detected unless trusted.
+            bs[HEADER_LEN + 16] = b'j';
+            std::fs::write(&path, &bs).unwrap();
+            assert!(matches!(FrozenArena::open_mmap(&path), Err(Error::ChecksumMismatch)));
+            let fa = FrozenArena::open_mmap_trusted(&path).unwrap();
+            assert_eq!(fa.resolve(id1), "jello");
+            drop(fa);
+            // Offsets are validated unless trusted, except bounds of strings.
+            let mut plain = bs[..bs.len() - 4].to_vec();
+            plain[10] = 0;
+            plain[HEADER_LEN + 4] = 6;
+            std::fs::write(&path, &plain).unwrap();
+            assert!(matches!(FrozenArena::open_mmap(&path), Err(Error::InvalidFormat)));
+            bs[HEADER_LEN + 12] = 7;
+            std::fs::write(&path, &bs).unwrap();
+            assert!(matches!(FrozenArena::open_mmap_trusted(&path), Err(Error::InvalidFormat)));
+            bs[8] = 2;
+            std::fs::write(&path, &bs).unwrap();
+            assert!(matches!(FrozenArena::open_mmap(&path), Err(Error::UnsupportedVersion(2))));
+            std::fs::write(&path, b"AOSA").unwrap();
+            assert!(matches!(FrozenArena::open_mmap(&path), Err(Error::InvalidFormat)));
+            std::fs::remove_file(&path).unwrap();
+            assert!(matches!(FrozenArena::open_mmap(&path), Err(Error::Io(_))));
+        }
+    }
+
+    #[cfg(all(feature = "lz4", feature = "mmap", unix))]
+    #[test]
+    fn test_save_to_compressed() {
+        let path = std::env::temp_dir().join(format!("aosa-lz4-{}", std::process::id()));
+        let words = ["alpha", "beta", "gamma", "delta", "世界"];
+        let ia = IndexedStringArena::with_capacity(1 << 16);
+        let ids: Vec<_> = (0..1000).map(|i| ia.add_id(words[i % 5]).unwrap()).collect();
+        ia.save_to_compressed(&path).unwrap();
+        let bs = std::fs::read(&path).unwrap();
+        assert_eq!(bs[10], FLAG_CHECKSUM | FLAG_COMPRESSED);
+        assert!(bs.len() < HEADER_LEN + 4004 + ia.len() / 5);
+        // SAFETY:
+        //
+        // The file is only written by this test, after snapshots of it are
+        // dropped.
+        unsafe {
+            let fa = FrozenArena::open_mmap(&path).unwrap();
+            assert_eq!(fa.len(), ia.len());
+            assert!(ids.iter().enumerate().all(|(i, &id)| fa.resolve(id) == words[i % 5]));
+            drop(fa);
+            // Corrupted blocks are rejected even if trusted.
+            let mut bad = bs.clone();
+            bad[10] = FLAG_COMPRESSED;
+            let n = bad.len() - 4;
+            bad.truncate(n - 1);
+            std::fs::write(&path, &bad).unwrap();
+            assert!(matches!(FrozenArena::open_mmap_trusted(&path), Err(Error::InvalidFormat)));
+            // Bytes inside strings are validated even if trusted.
+            let mut bad = bs[..bs.len() - 4].to_vec();
+            bad[10] = FLAG_COMPRESSED;
+            let n = bad.len();
+            bad[n - 2] = 0xff;
+            std::fs::write(&path, &bad).unwrap();
+            assert!(matches!(FrozenArena::open_mmap_trusted(&path), Err(Error::InvalidUtf8(_))));
+            // Empty arenas round trip.
+            IndexedStringArena::with_capacity(0).save_to_compressed(&path).unwrap();
+            assert!(FrozenArena::open_mmap(&path).unwrap().is_empty());
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
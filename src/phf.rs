@@ -0,0 +1,187 @@
+use crate::{FrozenArena, StrId};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Average number of strings of each bucket.
+const LAMBDA: usize = 4;
+
+/// Maximum number of seeds tried before giving up.
+const MAX_SEEDS: u64 = 64;
+
+/// Entry of table that no string maps to.
+const NONE: u32 = u32::MAX;
+
+/// PerfectHashIndex is a minimal perfect hash over distinct strings of a
+/// [`FrozenArena`], built by hash and displace.
+///
+/// Each string hashes to its own slot of a table of the same size, with
+/// only a pair of displacements stored for every few strings, so lookup
+/// costs one hash and one comparison of strings, without collisions.
+#[derive(Clone)]
+pub struct PerfectHashIndex {
+    arena: FrozenArena,
+    seed: u64,
+    // Displacements of each bucket.
+    disps: Vec<(u32, u32)>,
+    // Index of string in each slot.
+    map: Vec<u32>,
+}
+
+impl PerfectHashIndex {
+    /// Returns the frozen arena indexed.
+    #[inline]
+    pub fn arena(&self) -> &FrozenArena {
+        &self.arena
+    }
+
+    /// Returns number of distinct strings indexed.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns whether nothing is indexed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the handle of the string if exists. The handle of the first
+    /// occurrence is returned if a string is stored more than once.
+    #[inline]
+    pub fn lookup<T: AsRef<str>>(&self, s: T) -> Option<StrId> {
+        if self.map.is_empty() {
+            return None;
+        }
+        let s = s.as_ref();
+        let (g, f1, f2) = hashes(s.as_bytes(), self.seed);
+        let (d1, d2) = self.disps[g as usize % self.disps.len()];
+        let i = self.map[displace(f1, f2, d1, d2) as usize % self.map.len()];
+        let id = StrId::new(i, self.arena.generation());
+        (self.arena.resolve(id) == s).then_some(id)
+    }
+}
+
+impl FrozenArena {
+    /// Build a minimal perfect hash over distinct strings of the snapshot,
+    /// for lookup of handles without a hash table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no perfect hash is found, which is extremely unlikely.
+    pub fn perfect_hash(&self) -> PerfectHashIndex {
+        // Keep only the first occurrence of each string.
+        let mut sorted: Vec<(&str, u32)> = self.iter().zip(0..).collect();
+        sorted.sort_unstable();
+        sorted.dedup_by_key(|(s, _)| *s);
+        let n = sorted.len();
+        let nb = n.div_ceil(LAMBDA).max(1);
+        for seed in (0..MAX_SEEDS).map(|i| mix(i.wrapping_add(0x9e37_79b9_7f4a_7c15))) {
+            if let Some((disps, map)) = try_build(&sorted, nb, seed) {
+                return PerfectHashIndex { arena: self.clone(), seed, disps, map };
+            }
+        }
+        panic!("no perfect hash found")
+    }
+}
+
+/// Displacements of buckets, and index of string in each slot.
+type Tables = (Vec<(u32, u32)>, Vec<u32>);
+
+/// Try to find displacements of all buckets with given seed.
+fn try_build(strs: &[(&str, u32)], nb: usize, seed: u64) -> Option<Tables> {
+    let n = strs.len();
+    let hs: Vec<(u32, u32, u32)> = strs.iter().map(|(s, _)| hashes(s.as_bytes(), seed)).collect();
+    let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); nb];
+    for (k, h) in hs.iter().enumerate() {
+        buckets[h.0 as usize % nb].push(k);
+    }
+    let mut order: Vec<usize> = (0..nb).collect();
+    order.sort_unstable_by_key(|&b| core::cmp::Reverse(buckets[b].len()));
+    let mut disps = vec![(0, 0); nb];
+    let mut map = vec![NONE; n];
+    // Attempt marks of slots, so slots taken by a failed attempt need no
+    // reset.
+    let mut tried = vec![0u64; n];
+    let mut attempt = 0;
+    let span = u32::try_from(n).ok()?;
+    for b in order {
+        let keys = &buckets[b];
+        if keys.is_empty() {
+            continue;
+        }
+        // Vary d1 first, so consecutive attempts scatter over the table.
+        let found = (0..span).flat_map(|d2| (0..span).map(move |d1| (d1, d2))).find(|&(d1, d2)| {
+            attempt += 1;
+            keys.iter().all(|&k| {
+                let (_, f1, f2) = hs[k];
+                let slot = displace(f1, f2, d1, d2) as usize % n;
+                let free = map[slot] == NONE && tried[slot] != attempt;
+                tried[slot] = attempt;
+                free
+            })
+        })?;
+        disps[b] = found;
+        for &k in keys {
+            let (_, f1, f2) = hs[k];
+            map[displace(f1, f2, found.0, found.1) as usize % n] = strs[k].1;
+        }
+    }
+    Some((disps, map))
+}
+
+#[inline]
+fn displace(f1: u32, f2: u32, d1: u32, d2: u32) -> u32 {
+    d2.wrapping_add(f1.wrapping_mul(d1)).wrapping_add(f2)
+}
+
+/// Returns the bucket hash and two slot hashes of the bytes.
+#[inline]
+fn hashes(bs: &[u8], seed: u64) -> (u32, u32, u32) {
+    const K: u64 = 0x517c_c1b7_2722_0a95;
+    let mut h = seed ^ (bs.len() as u64).wrapping_mul(K);
+    let mut chunks = bs.chunks_exact(8);
+    for c in &mut chunks {
+        h = (h ^ u64::from_le_bytes(c.try_into().unwrap())).wrapping_mul(K).rotate_left(31);
+    }
+    let mut tail = [0u8; 8];
+    tail[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    h = (h ^ u64::from_le_bytes(tail)).wrapping_mul(K).rotate_left(31);
+    let (h1, h2) = (mix(h), mix(h ^ seed.rotate_left(17)));
+    ((h1 >> 32) as u32, h1 as u32, h2 as u32)
+}
+
+/// Finalizer of splitmix64.
+#[inline]
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IndexedStringArena;
+
+    #[test]
+    fn test_perfect_hash() {
+        let ia = IndexedStringArena::with_capacity(16 * 1024);
+        let ids: Vec<StrId> = (0..1000).map(|i| ia.add_id(format!("kw{}", i)).unwrap()).collect();
+        let dup = ia.add_id("kw7").unwrap();
+        let empty = ia.add_id("").unwrap();
+        let fa = ia.freeze();
+        let ph = fa.perfect_hash();
+        assert_eq!(ph.len(), 1001);
+        for (i, &id) in ids.iter().enumerate() {
+            assert_eq!(ph.lookup(format!("kw{}", i)), Some(id));
+        }
+        assert_ne!(ph.lookup("kw7"), Some(dup));
+        assert_eq!(ph.lookup(""), Some(empty));
+        assert_eq!(ph.lookup("kw1000"), None);
+        assert_eq!(ph.lookup("missing"), None);
+        let ph = IndexedStringArena::with_capacity(0).freeze().perfect_hash();
+        assert!(ph.is_empty());
+        assert_eq!(ph.lookup("a"), None);
+    }
+}
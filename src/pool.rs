@@ -0,0 +1,206 @@
+//! Pool of reusable arenas, which requires `std`.
+use crate::StringArena;
+use core::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+/// ShrinkPolicy decides what to do with an arena which grows beyond
+/// maximum retained capacity of the pool when it is returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShrinkPolicy {
+    /// Free the arena.
+    #[default]
+    Discard,
+    /// Reallocate the arena to the initial capacity and retain it.
+    Shrink,
+}
+
+/// ArenaPool is a thread-safe pool of reusable [`StringArena`]s.
+///
+/// [`get`](Self::get) hands out a cleared arena, which is returned to the
+/// pool when the guard is dropped, so large buffers are not allocated
+/// repeatedly.
+pub struct ArenaPool {
+    idle: Mutex<Vec<StringArena>>,
+    capacity: usize,
+    max_idle: usize,
+    max_retained_capacity: usize,
+    shrink: ShrinkPolicy,
+}
+
+impl ArenaPool {
+    /// Create a new pool of arenas with given initial capacity.
+    /// At most 16 idle arenas are retained by default, and arenas grown
+    /// beyond the initial capacity are discarded.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        ArenaPool {
+            idle: Mutex::new(Vec::new()),
+            capacity,
+            max_idle: 16,
+            max_retained_capacity: capacity,
+            shrink: ShrinkPolicy::Discard,
+        }
+    }
+
+    /// Set maximum number of idle arenas retained.
+    #[inline]
+    pub fn max_idle(mut self, max_idle: usize) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// Set maximum capacity of arenas retained, and what to do with
+    /// arenas exceeding it.
+    #[inline]
+    pub fn max_retained_capacity(mut self, cap: usize, shrink: ShrinkPolicy) -> Self {
+        self.max_retained_capacity = cap.max(self.capacity);
+        self.shrink = shrink;
+        self
+    }
+
+    /// Returns initial capacity of arenas.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns number of idle arenas in the pool.
+    #[inline]
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Returns a cleared arena, reused from the pool if possible.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn get(&self) -> PooledArena<'_> {
+        let arena = self.idle.lock().unwrap().pop();
+        let arena = arena.unwrap_or_else(|| StringArena::with_capacity(self.capacity));
+        PooledArena {
+            arena: Some(arena),
+            pool: self,
+        }
+    }
+
+    #[inline]
+    fn put(&self, mut arena: StringArena) {
+        arena.clear();
+        // Settings of the borrower must not leak to the next one.
+        arena.set_observer(None);
+        arena.set_budget(None).unwrap();
+        if arena.capacity() > self.max_retained_capacity {
+            match self.shrink {
+                ShrinkPolicy::Discard => return,
+                ShrinkPolicy::Shrink => {
+                    // Free the large buffer before allocating the small one.
+                    let res = arena.shrink_to_fit();
+                    if res.and_then(|_| arena.grow_to(self.capacity)).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.max_idle {
+            idle.push(arena);
+        }
+    }
+}
+
+/// PooledArena is an arena borrowed from [`ArenaPool`], which is returned
+/// to the pool on drop.
+pub struct PooledArena<'a> {
+    arena: Option<StringArena>,
+    pool: &'a ArenaPool,
+}
+
+impl Deref for PooledArena<'_> {
+    type Target = StringArena;
+
+    #[inline]
+    fn deref(&self) -> &StringArena {
+        self.arena.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledArena<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut StringArena {
+        self.arena.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledArena<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(arena) = self.arena.take() {
+            self.pool.put(arena);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena_pool() {
+        let pool = ArenaPool::new(16).max_idle(1);
+        let a1 = pool.get();
+        let a2 = pool.get();
+        a1.add("hello").unwrap();
+        a2.add("world").unwrap();
+        let ptr = a1.add("!").unwrap().as_ptr();
+        drop(a1);
+        drop(a2);
+        assert_eq!(pool.idle(), 1);
+        let a3 = pool.get();
+        assert!(a3.is_empty());
+        assert_eq!(a3.add("x").unwrap().as_ptr(), ptr.wrapping_sub(5));
+        drop(a3);
+        let mut a4 = pool.get();
+        a4.reserve(64).unwrap();
+        drop(a4);
+        assert_eq!(pool.idle(), 0);
+
+        let pool = ArenaPool::new(16).max_retained_capacity(32, ShrinkPolicy::Shrink);
+        let mut a5 = pool.get();
+        a5.grow_to(64).unwrap();
+        drop(a5);
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(pool.get().capacity(), 16);
+    }
+
+    #[test]
+    fn test_arena_pool_resets_settings() {
+        use crate::{MemoryBudget, Observer};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Default)]
+        struct Adds(AtomicUsize);
+
+        impl Observer for Adds {
+            fn on_add(&self, _len: usize) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let pool = ArenaPool::new(16);
+        let adds = Arc::new(Adds::default());
+        let budget = MemoryBudget::new(4);
+        let mut a1 = pool.get();
+        a1.set_observer(Some(adds.clone()));
+        a1.set_budget(Some(budget.clone())).unwrap();
+        a1.add("abc").unwrap();
+        drop(a1);
+        assert_eq!(budget.used(), 0);
+        let a2 = pool.get();
+        a2.add("hello world").unwrap();
+        assert_eq!(adds.0.load(Ordering::Relaxed), 1);
+        assert_eq!(budget.used(), 0);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{sys, Error, Result};
+use core::cell::Cell;
+use core::ptr::{self, NonNull};
+
+/// Granularity of committing reserved memory, which is a multiple of
+/// page size on all supported platforms.
+const COMMIT_UNIT: usize = 64 * 1024;
+
+/// ReservedStringArena is a single-thread append-only string arena
+/// which reserves a large virtual address range up front and commits
+/// memory on demand.
+///
+/// The buffer never moves, so it grows without chunking while all
+/// string refs stay valid, and only committed memory counts towards
+/// resident memory.
+pub struct ReservedStringArena {
+    ptr: NonNull<u8>,
+    reserved: usize,
+    committed: Cell<usize>,
+    idx: Cell<usize>,
+}
+
+// SAFETY:
+//
+// The mapping is exclusively owned by the arena.
+unsafe impl Send for ReservedStringArena {}
+
+impl ReservedStringArena {
+    /// Create a new arena reserving given bytes of address space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reservation fails.
+    #[inline]
+    pub fn with_reserved(reserved: usize) -> Self {
+        Self::try_with_reserved(reserved).unwrap()
+    }
+
+    /// Try to create a new arena reserving given bytes of address space.
+    /// Returns error if the reservation fails.
+    #[inline]
+    pub fn try_with_reserved(reserved: usize) -> Result<Self> {
+        let reserved = reserved
+            .checked_next_multiple_of(COMMIT_UNIT)
+            .ok_or(Error::AllocFailed(reserved))?;
+        let ptr = if reserved == 0 {
+            NonNull::dangling()
+        } else {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            let flags = sys::MAP_PRIVATE | sys::MAP_ANONYMOUS | sys::MAP_NORESERVE;
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            let flags = sys::MAP_PRIVATE | sys::MAP_ANONYMOUS;
+            // SAFETY:
+            //
+            // Anonymous mapping does not touch existing memory.
+            let ptr = unsafe {
+                sys::mmap(ptr::null_mut(), reserved, sys::PROT_NONE, flags, -1, 0)
+            };
+            if ptr == sys::MAP_FAILED {
+                return Err(Error::AllocFailed(reserved));
+            }
+            NonNull::new(ptr as *mut u8).ok_or(Error::AllocFailed(reserved))?
+        };
+        Ok(ReservedStringArena {
+            ptr,
+            reserved,
+            committed: Cell::new(0),
+            idx: Cell::new(0),
+        })
+    }
+
+    /// Returns bytes written of current arena.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.idx.get()
+    }
+
+    /// Returns whether the arena is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns bytes of address space reserved, which is the maximum
+    /// capacity of current arena.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.reserved
+    }
+
+    /// Returns bytes of memory committed.
+    #[inline]
+    pub fn committed(&self) -> usize {
+        self.committed.get()
+    }
+
+    /// Add a string into current arena.
+    /// Returns the string ref if succeeds.
+    /// Memory is committed if needed, and failure is returned if it
+    /// exceeds reserved capacity or OS fails to commit memory.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        let bs = self.add_bytes(s.as_ref().as_bytes())?;
+        // SAFETY:
+        //
+        // The bytes are copied from valid UTF-8.
+        unsafe { Ok(core::str::from_utf8_unchecked(bs)) }
+    }
+
+    /// Add raw bytes into current arena.
+    /// Returns the byte slice ref if succeeds.
+    #[inline]
+    pub fn add_bytes<T: AsRef<[u8]>>(&self, bs: T) -> Result<&[u8]> {
+        let bs = bs.as_ref();
+        let len = bs.len();
+        let idx = self.len();
+        let new_len = len + idx;
+        if self.reserved < new_len {
+            return Err(Error::ExceedsCapacity(new_len - self.reserved));
+        }
+        if self.committed() < new_len {
+            self.commit(new_len)?;
+        }
+        // SAFETY:
+        //
+        // The region after idx is committed and never referenced.
+        unsafe {
+            let dst = self.ptr.as_ptr().add(idx);
+            ptr::copy_nonoverlapping(bs.as_ptr(), dst, len);
+            self.idx.set(new_len);
+            Ok(core::slice::from_raw_parts(dst, len))
+        }
+    }
+
+    /// Clear the arena so its memory can be reused.
+    /// Committed memory is retained.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.idx.set(0);
+    }
+
+    #[inline]
+    fn commit(&self, len: usize) -> Result<()> {
+        let start = self.committed();
+        // Reserved length is a multiple of commit unit, so it never overflows.
+        let end = len.next_multiple_of(COMMIT_UNIT);
+        // SAFETY:
+        //
+        // The range is within the reservation and page-aligned.
+        let res = unsafe {
+            sys::mprotect(
+                self.ptr.as_ptr().add(start) as *mut _,
+                end - start,
+                sys::PROT_READ | sys::PROT_WRITE,
+            )
+        };
+        if res != 0 {
+            return Err(Error::AllocFailed(end - start));
+        }
+        self.committed.set(end);
+        Ok(())
+    }
+}
+
+impl Drop for ReservedStringArena {
+    #[inline]
+    fn drop(&mut self) {
+        if self.reserved != 0 {
+            // SAFETY:
+            //
+            // The range is mapped in constructor.
+            unsafe { sys::munmap(self.ptr.as_ptr() as *mut _, self.reserved) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_string_arena() {
+        // Reserve 64 GiB of address space.
+        let sa = ReservedStringArena::with_reserved(64 << 30);
+        assert_eq!(sa.committed(), 0);
+        let s1 = sa.add("hello").unwrap();
+        assert_eq!(sa.committed(), COMMIT_UNIT);
+        let big = "x".repeat(COMMIT_UNIT * 2);
+        let s2 = sa.add(&big).unwrap();
+        assert_eq!(sa.committed(), COMMIT_UNIT * 3);
+        assert_eq!(s1, "hello");
+        assert_eq!(s2, big);
+
+        let mut sa = ReservedStringArena::with_reserved(10);
+        assert_eq!(sa.capacity(), COMMIT_UNIT);
+        assert!(sa.add(&big).is_err());
+        sa.clear();
+        assert!(sa.is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{BackingAlloc, Result, StringArena};
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+/// ArenaRope is a text built by concatenating string refs, e.g. those
+/// stored in [`StringArena`], without copying them.
+///
+/// Only the list of pieces grows on each concatenation. The text is
+/// copied once into an arena by [`materialize`](Self::materialize), when
+/// a contiguous string is needed.
+#[derive(Debug, Clone, Default)]
+pub struct ArenaRope<'a> {
+    pieces: Vec<&'a str>,
+    len: usize,
+}
+
+impl<'a> ArenaRope<'a> {
+    /// Create a new empty rope.
+    #[inline]
+    pub fn new() -> Self {
+        ArenaRope::default()
+    }
+
+    /// Returns bytes of the text.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the text is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns all pieces of the text in order.
+    #[inline]
+    pub fn pieces(&self) -> &[&'a str] {
+        &self.pieces
+    }
+
+    /// Append a string ref to the end of the text.
+    /// Empty strings are skipped.
+    #[inline]
+    pub fn push(&mut self, s: &'a str) {
+        if !s.is_empty() {
+            self.pieces.push(s);
+            self.len += s.len();
+        }
+    }
+
+    /// Append all pieces of another rope to the end of the text.
+    #[inline]
+    pub fn append(&mut self, other: &ArenaRope<'a>) {
+        self.pieces.extend_from_slice(&other.pieces);
+        self.len += other.len;
+    }
+
+    /// Returns the text as a contiguous string.
+    /// A text of single piece is returned as is, otherwise all pieces are
+    /// copied into given arena.
+    /// Nothing is kept in the arena if it exceeds capacity.
+    pub fn materialize<'b, A: BackingAlloc>(&self, arena: &'b StringArena<A>) -> Result<&'b str>
+    where
+        'a: 'b,
+    {
+        if let [s] = self.pieces[..] {
+            return Ok(s);
+        }
+        let mut w = arena.writer();
+        for s in &self.pieces {
+            if w.write_str(s).is_err() {
+                break;
+            }
+        }
+        w.finish()
+    }
+
+    /// Clear the text, keeping memory of the piece list.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.pieces.clear();
+        self.len = 0;
+    }
+}
+
+impl<'a> Extend<&'a str> for ArenaRope<'a> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|s| self.push(s));
+    }
+}
+
+impl<'a> FromIterator<&'a str> for ArenaRope<'a> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        let mut rope = ArenaRope::new();
+        rope.extend(iter);
+        rope
+    }
+}
+
+impl fmt::Display for ArenaRope<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pieces.iter().try_for_each(|s| f.write_str(s))
+    }
+}
+
+impl PartialEq<str> for ArenaRope<'_> {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        let mut rest = other.as_bytes();
+        self.len == other.len()
+            && self.pieces.iter().all(|s| match rest.strip_prefix(s.as_bytes()) {
+                Some(r) => {
+                    rest = r;
+                    true
+                }
+                None => false,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena_rope() {
+        let sa = StringArena::with_capacity(64);
+        let (name, greet) = (sa.add("world").unwrap(), sa.add("hello").unwrap());
+        let mut head = ArenaRope::new();
+        head.push(greet);
+        head.push("");
+        assert_eq!(head.pieces().len(), 1);
+        assert!(std::ptr::eq(head.materialize(&sa).unwrap(), greet));
+        let mut rope: ArenaRope<'_> = [", ", name].into_iter().collect();
+        let mut tail = ArenaRope::new();
+        tail.push("!");
+        rope.append(&tail);
+        head.append(&rope);
+        assert_eq!(head.len(), 13);
+        assert!(head == *"hello, world!");
+        assert!(head != *"hello, world?");
+        assert_eq!(head.to_string(), "hello, world!");
+        let len = sa.len();
+        assert_eq!(head.materialize(&sa).unwrap(), "hello, world!");
+        assert_eq!(sa.len(), len + 13);
+        let small = StringArena::with_capacity(8);
+        assert!(head.materialize(&small).is_err());
+        assert!(small.is_empty());
+        head.clear();
+        assert_eq!(head.materialize(&small).unwrap(), "");
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{BackingAlloc, Global, Mark, Result, StringArena};
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+/// ScopedArena is a [`StringArena`] only accessible inside the closure
+/// passed to [`StringArena::scope`].
+///
+/// Strings added are bound to the brand lifetime `'id`, which is
+/// invariant and unique to each scope, so the borrow checker rejects any
+/// string ref escaping the closure, or mixing strings of nested scopes.
+#[derive(Clone, Copy)]
+pub struct ScopedArena<'id> {
+    arena: &'id StringArena,
+    _brand: PhantomData<fn(&'id ()) -> &'id ()>,
+}
+
+impl<'id> ScopedArena<'id> {
+    /// Add a string into the scoped arena.
+    /// Returns the string ref bound to the scope if succeeds.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> Result<&'id str> {
+        self.arena.add(s)
+    }
+
+    /// Returns the underlying arena, whose strings are bound to the scope.
+    #[inline]
+    pub fn arena(&self) -> &'id StringArena {
+        self.arena
+    }
+}
+
+impl Deref for ScopedArena<'_> {
+    type Target = StringArena;
+
+    #[inline]
+    fn deref(&self) -> &StringArena {
+        self.arena
+    }
+}
+
+impl StringArena {
+    /// Run the closure with a new arena of given capacity, which is freed
+    /// when the closure returns, even if it panics. No string ref of the
+    /// arena can be returned by the closure.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn scope<R>(cap: usize, f: impl for<'id> FnOnce(ScopedArena<'id>) -> R) -> R {
+        Self::try_scope(cap, f).unwrap()
+    }
+
+    /// Run the closure like [`scope`](Self::scope).
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_scope<R>(cap: usize, f: impl for<'id> FnOnce(ScopedArena<'id>) -> R) -> Result<R> {
+        let arena = StringArena::try_with_capacity(cap)?;
+        Ok(f(ScopedArena { arena: &arena, _brand: PhantomData }))
+    }
+}
+
+/// ScopeGuard is a nested scope of a [`StringArena`], returned by
+/// [`StringArena::push_scope`].
+///
+/// It gives exclusive access to the arena, and discards all strings added
+/// inside the scope when dropped, or popped by
+/// [`pop_scope`](Self::pop_scope). Scopes can be nested by pushing on the
+/// guard, and are popped in stack order.
+pub struct ScopeGuard<'a, A: BackingAlloc = Global> {
+    arena: &'a mut StringArena<A>,
+    mark: Mark,
+}
+
+impl<A: BackingAlloc> ScopeGuard<'_, A> {
+    /// Returns the mark of the arena when the scope is pushed.
+    #[inline]
+    pub fn mark(&self) -> Mark {
+        self.mark
+    }
+
+    /// Pop the scope, discarding all strings added inside it.
+    #[inline]
+    pub fn pop_scope(self) {}
+}
+
+impl<A: BackingAlloc> Deref for ScopeGuard<'_, A> {
+    type Target = StringArena<A>;
+
+    #[inline]
+    fn deref(&self) -> &StringArena<A> {
+        self.arena
+    }
+}
+
+impl<A: BackingAlloc> DerefMut for ScopeGuard<'_, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut StringArena<A> {
+        self.arena
+    }
+}
+
+impl<A: BackingAlloc> Drop for ScopeGuard<'_, A> {
+    #[inline]
+    fn drop(&mut self) {
+        self.arena.truncate_to(self.mark);
+    }
+}
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Push a nested scope, whose strings are discarded when the returned
+    /// guard is dropped.
+    #[inline]
+    pub fn push_scope(&mut self) -> ScopeGuard<'_, A> {
+        ScopeGuard { mark: self.checkpoint(), arena: self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoped_arena() {
+        let len = StringArena::scope(64, |sa| {
+            let s1 = sa.add("hello").unwrap();
+            let s2 = sa.arena().add_fmt(format_args!("{}-{}", s1, 42)).unwrap();
+            assert_eq!(s2, "hello-42");
+            let inner = StringArena::scope(8, |inner| inner.add(s1).unwrap().len());
+            assert_eq!(inner, 5);
+            assert!(sa.add("x".repeat(64)).is_err());
+            sa.len()
+        });
+        assert_eq!(len, 13);
+        assert!(StringArena::try_scope(usize::MAX, |_| ()).is_err());
+    }
+
+    #[test]
+    fn test_scope_guard() {
+        let mut sa = StringArena::with_capacity(64);
+        sa.add("root").unwrap();
+        {
+            let mut outer = sa.push_scope();
+            assert_eq!(outer.mark().offset(), 4);
+            outer.add("outer").unwrap();
+            let inner = outer.push_scope();
+            assert_eq!(inner.add("inner").unwrap(), "inner");
+            assert_eq!(inner.len(), 14);
+            inner.pop_scope();
+            assert_eq!(outer.len(), 9);
+        }
+        assert_eq!(sa.len(), 4);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{Result, StringArena};
+use core::fmt;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// SecretStringArena is a string arena for sensitive data, which zeroes
+/// its buffer when cleared or dropped.
+///
+/// The whole buffer is overwritten with volatile writes, so bytes left by
+/// failed adds are also erased, and the compiler cannot elide the writes.
+/// With feature `mlock`, the buffer can also be locked into memory so it
+/// is never swapped out.
+pub struct SecretStringArena {
+    arena: StringArena,
+    #[cfg(all(feature = "mlock", unix))]
+    locked: bool,
+}
+
+impl SecretStringArena {
+    /// Create a new secret string arena with given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::try_with_capacity(cap).unwrap()
+    }
+
+    /// Try to create a new secret string arena with given capacity.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity(cap: usize) -> Result<Self> {
+        Ok(SecretStringArena {
+            arena: StringArena::try_with_capacity(cap)?,
+            #[cfg(all(feature = "mlock", unix))]
+            locked: false,
+        })
+    }
+
+    /// Lock the buffer into memory, so it is never swapped out.
+    /// Fails if the limit of locked memory is exceeded or the process is
+    /// not permitted, and the arena can still be used unlocked.
+    #[cfg(all(feature = "mlock", unix))]
+    #[inline]
+    pub fn lock(&mut self) -> Result<()> {
+        let cap = self.arena.capacity();
+        if self.locked || cap == 0 {
+            return Ok(());
+        }
+        // SAFETY:
+        //
+        // The region is the buffer owned by the arena.
+        if unsafe { crate::sys::mlock(self.arena.as_mut_ptr() as *const _, cap) } != 0 {
+            return Err(crate::Error::LockFailed(cap));
+        }
+        self.locked = true;
+        Ok(())
+    }
+
+    /// Returns whether the buffer is locked into memory.
+    #[cfg(all(feature = "mlock", unix))]
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Returns bytes written of current arena.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns whether the arena is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    /// Returns capacity of current arena.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    /// Add a string into current arena.
+    /// Returns the string ref if succeeds.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        self.arena.add(s)
+    }
+
+    /// Add raw bytes into current arena.
+    /// Returns the byte slice ref if succeeds.
+    #[inline]
+    pub fn add_bytes<T: AsRef<[u8]>>(&self, bs: T) -> Result<&[u8]> {
+        self.arena.add_bytes(bs)
+    }
+
+    /// Format a string directly into current arena.
+    /// Returns the string ref if succeeds.
+    #[inline]
+    pub fn add_fmt(&self, args: fmt::Arguments<'_>) -> Result<&str> {
+        self.arena.add_fmt(args)
+    }
+
+    /// Zero the buffer and clear the arena so its memory can be reused.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.arena.clear();
+        self.zeroize();
+    }
+
+    /// Zero the buffer and renew the arena for future usage.
+    /// This is safe because all string refs associated to this arena
+    /// must be dropped before this method call.
+    #[inline]
+    pub fn renew(mut self) -> Self {
+        self.clear();
+        self
+    }
+
+    fn zeroize(&mut self) {
+        let ptr = self.arena.as_mut_ptr();
+        for i in 0..self.arena.capacity() {
+            // SAFETY:
+            //
+            // The pointer is within the buffer exclusively borrowed.
+            unsafe { ptr.add(i).write_volatile(0) };
+        }
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+impl Drop for SecretStringArena {
+    #[inline]
+    fn drop(&mut self) {
+        self.zeroize();
+        #[cfg(all(feature = "mlock", unix))]
+        if self.locked {
+            let cap = self.arena.capacity();
+            // SAFETY:
+            //
+            // The region is locked in lock().
+            unsafe { crate::sys::munlock(self.arena.as_mut_ptr() as *const _, cap) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_string_arena() {
+        let mut sa = SecretStringArena::with_capacity(16);
+        assert_eq!(sa.add("password").unwrap(), "password");
+        assert!(sa.add_fmt(format_args!("{}{}", "12345678", "9")).is_err());
+        sa.clear();
+        assert!(sa.is_empty());
+        // SAFETY:
+        //
+        // The buffer is not referenced, and all bytes are initialized.
+        let bs = unsafe { core::slice::from_raw_parts(sa.arena.as_mut_ptr(), 16) };
+        assert!(bs.iter().all(|&b| b == 0));
+    }
+
+    #[cfg(all(feature = "mlock", unix))]
+    #[test]
+    fn test_secret_string_arena_lock() {
+        let mut sa = SecretStringArena::with_capacity(4096);
+        // Locking may be forbidden by the limit, which falls back to unlocked.
+        match sa.lock() {
+            Ok(()) => assert!(sa.is_locked()),
+            Err(e) => {
+                assert!(matches!(e, crate::Error::LockFailed(4096)));
+                assert!(!sa.is_locked());
+            }
+        }
+        assert_eq!(sa.add("key").unwrap(), "key");
+    }
+}
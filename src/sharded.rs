@@ -0,0 +1,492 @@
+use crate::{BackingAlloc, Global, Result, Span, StringArena};
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::hash::BuildHasher;
+
+/// ShardedId is a handle of a string stored in [`ShardedStringArena`],
+/// which packs the shard, the low 16 bits of its generation, and offset
+/// and length of the string in 12 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShardedId {
+    shard: u16,
+    generation: u16,
+    offset: u32,
+    len: u32,
+}
+
+impl ShardedId {
+    /// Returns index of the shard which stores the string.
+    #[inline]
+    pub fn shard(self) -> usize {
+        self.shard as usize
+    }
+
+    /// Returns offset of the string inside its shard.
+    #[inline]
+    pub fn offset(self) -> usize {
+        self.offset as usize
+    }
+
+    /// Returns length of the string.
+    #[inline]
+    pub fn len(self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns whether the string is empty.
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns span of the string in given shard, or None if the handle
+    /// is taken in another generation of the shard.
+    #[inline]
+    fn span_in<A: BackingAlloc>(self, arena: &StringArena<A>) -> Option<Span> {
+        let generation = arena.generation();
+        (self.generation == generation as u16)
+            .then(|| Span::tagged(self.offset(), self.len(), generation))
+    }
+}
+
+/// ShardedStringArena is a set of string arenas which can be filled by
+/// multiple threads without contention.
+///
+/// Each thread adds strings into its own shard through a [`ShardWriter`],
+/// returned by [`writers`](Self::writers). Handles carry the shard, so
+/// strings of all shards can be resolved by the sharded arena once writers
+/// are dropped.
+///
+/// Each shard can have its own backing allocator, e.g. mapping memory of
+/// the NUMA node of the thread writing it. The sharded arena can be frozen
+/// by [`freeze`](Self::freeze) so that all threads resolve handles.
+///
+/// With `std`, a batch of strings can be added by scoped threads of all
+/// shards at once, by `par_extend` or `par_intern_all`.
+pub struct ShardedStringArena<A: BackingAlloc = Global> {
+    shards: Vec<StringArena<A>>,
+}
+
+impl ShardedStringArena {
+    /// Create a new sharded arena with given number of shards and capacity
+    /// of each shard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails, there are more than 65536 shards,
+    /// or the capacity exceeds `u32::MAX`.
+    #[inline]
+    pub fn with_shards(shards: usize, cap: usize) -> Self {
+        Self::try_with_shards(shards, cap).unwrap()
+    }
+
+    /// Try to create a new sharded arena with given number of shards and
+    /// capacity of each shard.
+    /// Returns error if the allocation fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 65536 shards, or the capacity exceeds
+    /// `u32::MAX`.
+    #[inline]
+    pub fn try_with_shards(shards: usize, cap: usize) -> Result<Self> {
+        Self::try_with_shards_in(shards, cap, |_| Global)
+    }
+}
+
+impl<A: BackingAlloc> ShardedStringArena<A> {
+    /// Create a new sharded arena with given number of shards and capacity
+    /// of each shard, with backing allocator of each shard returned by
+    /// given function of the shard index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails, there are more than 65536 shards,
+    /// or the capacity exceeds `u32::MAX`.
+    #[inline]
+    pub fn with_shards_in<F: FnMut(usize) -> A>(shards: usize, cap: usize, f: F) -> Self {
+        Self::try_with_shards_in(shards, cap, f).unwrap()
+    }
+
+    /// Try to create a new sharded arena with given number of shards and
+    /// capacity of each shard, with backing allocator of each shard
+    /// returned by given function of the shard index.
+    /// Returns error if the allocation fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 65536 shards, or the capacity exceeds
+    /// `u32::MAX`.
+    #[inline]
+    pub fn try_with_shards_in<F: FnMut(usize) -> A>(
+        shards: usize,
+        cap: usize,
+        mut f: F,
+    ) -> Result<Self> {
+        // Shards and offsets of strings must fit in handles.
+        assert!(shards <= 1 << 16, "more than 65536 shards");
+        assert!(u32::try_from(cap).is_ok(), "shard capacity exceeds u32::MAX");
+        let shards = (0..shards)
+            .map(|i| StringArena::try_with_capacity_in(cap, f(i)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ShardedStringArena { shards })
+    }
+
+    /// Returns number of shards.
+    #[inline]
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns bytes written of all shards.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.len()).sum()
+    }
+
+    /// Returns whether all shards are empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.is_empty())
+    }
+
+    /// Returns one writer for each shard, which can be sent to threads.
+    /// The exclusive borrow guarantees no string is resolved until all
+    /// writers are dropped.
+    #[inline]
+    pub fn writers(&mut self) -> Vec<ShardWriter<'_, A>> {
+        self.shards
+            .iter_mut()
+            .enumerate()
+            .map(|(shard, arena)| ShardWriter { shard: shard as u16, arena })
+            .collect()
+    }
+
+    /// Resolve the handle to string ref.
+    /// Returns None if the handle is not returned by this arena.
+    #[inline]
+    pub fn get(&self, id: ShardedId) -> Option<&str> {
+        let arena = self.shards.get(id.shard())?;
+        arena.get(id.span_in(arena)?)
+    }
+
+    /// Freeze the sharded arena into a snapshot, which can be shared among
+    /// threads to resolve handles of all shards.
+    #[inline]
+    pub fn freeze(self) -> FrozenShards<A> {
+        FrozenShards { shards: self.shards }
+    }
+
+    /// Clear all shards so their memory can be reused.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.shards.iter_mut().for_each(StringArena::clear);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A: BackingAlloc + Send> ShardedStringArena<A> {
+    /// Add all strings in parallel, by one scoped thread for each shard,
+    /// and return their handles in order of strings.
+    ///
+    /// Strings are split into contiguous chunks, one for each shard.
+    /// Returns error if any shard is full, and strings added before the
+    /// failure are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if strings are given but there are no shards.
+    pub fn par_extend<T: AsRef<str> + Sync>(&mut self, strs: &[T]) -> Result<Vec<ShardedId>> {
+        let chunk = self.chunk_len(strs.len());
+        let ids = par_map(self.writers().into_iter().zip(strs.chunks(chunk)), |(mut w, strs)| {
+            strs.iter().map(|s| w.add(s)).collect::<Result<Vec<_>>>()
+        });
+        Ok(ids.into_iter().collect::<Result<Vec<_>>>()?.concat())
+    }
+
+    /// Intern all strings in parallel, by one scoped thread for each shard,
+    /// and return their handles in order of strings.
+    ///
+    /// Strings are routed to shards by hash, so equal strings share the
+    /// handle of the first one, and each distinct string is added once.
+    /// Strings added before this call are not looked up. Returns error if
+    /// any shard is full, and strings added before the failure are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if strings are given but there are no shards.
+    pub fn par_intern_all<T: AsRef<str> + Sync>(&mut self, strs: &[T]) -> Result<Vec<ShardedId>> {
+        let (chunk, shards) = (self.chunk_len(strs.len()), self.shards());
+        let state = RandomState::new();
+        // Indices of strings routed to each shard, for each chunk.
+        let routes = par_map(strs.chunks(chunk).enumerate(), |(c, strs)| {
+            let mut routes = alloc::vec![Vec::new(); shards];
+            for (i, s) in strs.iter().enumerate() {
+                routes[state.hash_one(s.as_ref()) as usize % shards].push(c * chunk + i);
+            }
+            routes
+        });
+        let ids = par_map(self.writers(), |mut w| {
+            let (mut seen, shard) = (HashMap::new(), w.shard());
+            routes
+                .iter()
+                .flat_map(|r| &r[shard])
+                .map(|&i| {
+                    let s = strs[i].as_ref();
+                    if let Some(&id) = seen.get(s) {
+                        return Ok(id);
+                    }
+                    let id = w.add(s)?;
+                    seen.insert(s, id);
+                    Ok(id)
+                })
+                .collect::<Result<Vec<_>>>()
+        });
+        let empty = ShardedId {
+            shard: 0,
+            generation: 0,
+            offset: 0,
+            len: 0,
+        };
+        let mut res = alloc::vec![empty; strs.len()];
+        for (shard, ids) in ids.into_iter().enumerate() {
+            let indices = routes.iter().flat_map(|r| &r[shard]);
+            for (&i, id) in indices.zip(ids?) {
+                res[i] = id;
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns number of strings in each chunk split for shards.
+    #[inline]
+    fn chunk_len(&self, strs: usize) -> usize {
+        assert!(strs == 0 || self.shards() > 0, "no shards to add strings");
+        strs.div_ceil(self.shards().max(1)).max(1)
+    }
+}
+
+/// Run the function on each item by a scoped thread, and returns results
+/// in order of items. Panics of threads are propagated.
+#[cfg(feature = "std")]
+fn par_map<I, F, R>(items: I, f: F) -> Vec<R>
+where
+    I: IntoIterator,
+    I::Item: Send,
+    F: Fn(I::Item) -> R + Sync,
+    R: Send,
+{
+    std::thread::scope(|s| {
+        let f = &f;
+        let handles: Vec<_> = items.into_iter().map(|item| s.spawn(move || f(item))).collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    })
+}
+
+/// ShardWriter adds strings into one shard of [`ShardedStringArena`].
+pub struct ShardWriter<'a, A: BackingAlloc = Global> {
+    shard: u16,
+    arena: &'a mut StringArena<A>,
+}
+
+impl<A: BackingAlloc> ShardWriter<'_, A> {
+    /// Returns index of the shard.
+    #[inline]
+    pub fn shard(&self) -> usize {
+        self.shard as usize
+    }
+
+    /// Add a string into the shard.
+    /// Returns the handle of the string if succeeds.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&mut self, s: T) -> Result<ShardedId> {
+        let span = self.arena.add_span(s)?;
+        // Capacity of the shard fits u32, so offset and length fit too.
+        Ok(ShardedId {
+            shard: self.shard,
+            generation: span.generation() as u16,
+            offset: span.offset() as u32,
+            len: span.len() as u32,
+        })
+    }
+
+    /// Returns the string of given handle if it is added by this writer.
+    #[inline]
+    pub fn get(&self, id: ShardedId) -> Option<&str> {
+        if id.shard != self.shard {
+            return None;
+        }
+        self.arena.get(id.span_in(self.arena)?)
+    }
+}
+
+/// FrozenShards is an immutable snapshot of [`ShardedStringArena`],
+/// returned by [`ShardedStringArena::freeze`].
+///
+/// Shards can no longer be written, so the snapshot can be shared among
+/// threads, e.g. in an `Arc`, and any thread can resolve handles of any
+/// shard.
+pub struct FrozenShards<A: BackingAlloc = Global> {
+    shards: Vec<StringArena<A>>,
+}
+
+// SAFETY:
+//
+// Shards are never modified once frozen, and only their written bytes
+// are read through shared refs. The allocators are only used on drop.
+unsafe impl<A: BackingAlloc + Send> Sync for FrozenShards<A> {}
+
+impl<A: BackingAlloc> FrozenShards<A> {
+    /// Returns number of shards.
+    #[inline]
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns bytes written of all shards.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.len()).sum()
+    }
+
+    /// Returns whether all shards are empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.is_empty())
+    }
+
+    /// Resolve the handle to string ref.
+    /// Returns None if the handle is not returned by the sharded arena.
+    #[inline]
+    pub fn get(&self, id: ShardedId) -> Option<&str> {
+        let arena = self.shards.get(id.shard())?;
+        arena.get(id.span_in(arena)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sharded_string_arena() {
+        let mut sa = ShardedStringArena::with_shards(4, 1024);
+        let ids: Vec<Vec<ShardedId>> = std::thread::scope(|s| {
+            let handles: Vec<_> = sa
+                .writers()
+                .into_iter()
+                .map(|mut w| {
+                    s.spawn(move || {
+                        (0..50)
+                            .map(|j| w.add(format!("{}-{}", w.shard(), j)).unwrap())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for (i, ids) in ids.iter().enumerate() {
+            for (j, id) in ids.iter().enumerate() {
+                assert_eq!(id.shard(), i);
+                assert_eq!(sa.get(*id).unwrap(), format!("{}-{}", i, j));
+            }
+        }
+        assert_eq!(sa.shards(), 4);
+        let mut ws = sa.writers();
+        assert_eq!(ws[1].get(ids[1][0]), Some("1-0"));
+        assert_eq!(ws[0].get(ids[1][0]), None);
+        assert!(ws[0].add("x".repeat(1024)).is_err());
+        drop(ws);
+        sa.clear();
+        assert!(sa.is_empty());
+    }
+
+    #[test]
+    fn test_frozen_shards() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        assert_eq!(std::mem::size_of::<ShardedId>(), 12);
+        let mut sa = ShardedStringArena::with_shards(2, 64);
+        let mut ws = sa.writers();
+        let ids = [ws[0].add("hello").unwrap(), ws[1].add("world").unwrap()];
+        drop(ws);
+        let mut ws = sa.writers();
+        ws[0].arena.clear();
+        let fresh = ws[0].add("again").unwrap();
+        assert_eq!(ws[0].get(ids[0]), None);
+        drop(ws);
+        let frozen = sa.freeze();
+        assert_send_sync(&frozen);
+        assert_eq!((frozen.shards(), frozen.len()), (2, 10));
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    assert_eq!(frozen.get(ids[1]), Some("world"));
+                    assert_eq!(frozen.get(fresh), Some("again"));
+                    assert_eq!(frozen.get(ids[0]), None);
+                });
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "more than 65536 shards")]
+    fn test_sharded_string_arena_too_many_shards() {
+        ShardedStringArena::try_with_shards(1 << 17, 0).ok();
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    #[should_panic(expected = "shard capacity exceeds u32::MAX")]
+    fn test_sharded_string_arena_too_large_shard() {
+        ShardedStringArena::try_with_shards(1, 1 << 33).ok();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_sharded_string_arena_par() {
+        let strs: Vec<String> = (0..1000).map(|i| format!("s{}", i % 100)).collect();
+        let mut sa = ShardedStringArena::with_shards(4, 4096);
+        let ids = sa.par_extend(&strs).unwrap();
+        assert!(ids.iter().zip(&strs).all(|(&id, s)| sa.get(id) == Some(s)));
+        assert_eq!((ids.len(), ids[0].shard(), ids[999].shard()), (1000, 0, 3));
+        sa.clear();
+        let ids = sa.par_intern_all(&strs).unwrap();
+        assert!(ids.iter().zip(&strs).all(|(&id, s)| sa.get(id) == Some(s)));
+        assert_eq!(ids[0], ids[100]);
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(sa.len(), 10 * 2 + 90 * 3);
+        let mut sa = ShardedStringArena::with_shards(2, 8);
+        assert!(sa.par_extend(&["hello", "world", "again"]).is_err());
+        let ids = sa.par_extend(&["hey"]).unwrap();
+        assert_eq!(sa.get(ids[0]), Some("hey"));
+        assert!(sa.par_intern_all::<&str>(&[]).unwrap().is_empty());
+        let mut sa = ShardedStringArena::with_shards(0, 8);
+        assert!(sa.par_extend::<&str>(&[]).unwrap().is_empty());
+    }
+
+    #[cfg(all(feature = "mmap", unix))]
+    #[test]
+    fn test_sharded_string_arena_in() {
+        use crate::{Mmap, NumaPolicy};
+
+        let mut sa = ShardedStringArena::with_shards_in(2, 1024, |i| {
+            Mmap::new().numa(NumaPolicy::Bind(i % 2))
+        });
+        let ids: Vec<ShardedId> = sa
+            .writers()
+            .iter_mut()
+            .map(|w| w.add("hello").unwrap())
+            .collect();
+        assert_eq!(sa.get(ids[1]), Some("hello"));
+    }
+}
@@ -0,0 +1,141 @@
+//! Sharing indexed arenas among processes by POSIX shared memory.
+use crate::{sys, Error, FrozenArena, IndexedStringArena, Result};
+use core::ffi::c_uint;
+use std::ffi::CString;
+use std::fs::File;
+use std::os::unix::io::FromRawFd;
+
+/// Returns the name as C string.
+#[inline]
+fn c_name(name: &str) -> Result<CString> {
+    CString::new(name).map_err(|e| Error::InteriorNul(e.nul_position()))
+}
+
+/// Open shared memory object of given name, returning it as file.
+#[inline]
+fn shm_open(name: &str, oflag: i32) -> Result<File> {
+    let name = c_name(name)?;
+    // SAFETY:
+    //
+    // The name is a valid C string, and mode is passed as promoted.
+    let fd = unsafe { sys::shm_open(name.as_ptr(), oflag, 0o600 as c_uint) };
+    if fd < 0 {
+        return Err(Error::Io(std::io::Error::last_os_error()));
+    }
+    // SAFETY:
+    //
+    // The descriptor is just opened and owned by the file.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+impl IndexedStringArena {
+    /// Save the arena to POSIX shared memory of given name, e.g.
+    /// `"/dict"`, which can be opened by other processes with
+    /// [`FrozenArena::open_shm`]. An existing object of the name is
+    /// unlinked and replaced by a new one, never written in place, so
+    /// snapshots opened before keep their bytes.
+    ///
+    /// The object persists until removed by [`FrozenArena::unlink_shm`].
+    /// Opening the name while it is being saved may fail with
+    /// [`Error::InvalidFormat`] or [`Error::Io`].
+    pub fn save_to_shm(&self, name: &str) -> Result<()> {
+        match FrozenArena::unlink_shm(name) {
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+            Ok(()) => (),
+        }
+        self.write_file(shm_open(name, sys::O_RDWR | sys::O_CREAT | sys::O_EXCL)?, None)
+    }
+}
+
+impl FrozenArena {
+    /// Open POSIX shared memory of given name saved by
+    /// [`IndexedStringArena::save_to_shm`] as a snapshot, by mapping it
+    /// read-only into memory.
+    /// Processes opening the same object share its pages, and handles
+    /// returned by the saved arena resolve to the same strings, e.g. sent
+    /// by [`StrId::to_bits`](crate::StrId::to_bits).
+    ///
+    /// # Safety
+    ///
+    /// The object is validated only once on opening, so it must not be
+    /// truncated or written while the snapshot is alive, by this or any
+    /// other process. Objects saved by [`IndexedStringArena::save_to_shm`]
+    /// are replaced instead of written, which is fine. Otherwise reading
+    /// strings may fault with a bus error, or observe bytes that are not
+    /// valid UTF-8.
+    pub unsafe fn open_shm(name: &str) -> Result<Self> {
+        Self::open_file(&shm_open(name, sys::O_RDONLY)?, true)
+    }
+
+    /// Open POSIX shared memory like [`FrozenArena::open_shm`], but skip
+    /// verifying the checksum, UTF-8 of strings and their offsets, for
+    /// objects saved by trusted processes.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`FrozenArena::open_shm`]. Besides, the object must be saved
+    /// by [`IndexedStringArena::save_to_shm`] without corruption, so offsets
+    /// are non-decreasing and at char boundaries of valid UTF-8 bytes.
+    pub unsafe fn open_shm_trusted(name: &str) -> Result<Self> {
+        Self::open_file(&shm_open(name, sys::O_RDONLY)?, false)
+    }
+
+    /// Remove POSIX shared memory of given name. Snapshots opened before
+    /// stay valid.
+    pub fn unlink_shm(name: &str) -> Result<()> {
+        let name = c_name(name)?;
+        // SAFETY:
+        //
+        // The name is a valid C string.
+        if unsafe { sys::shm_unlink(name.as_ptr()) } != 0 {
+            return Err(Error::Io(std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StrId;
+
+    #[test]
+    fn test_shared_memory_arena() {
+        let name = format!("/aosa-shm-{}", std::process::id());
+        let ia = IndexedStringArena::with_capacity(64);
+        let ids: Vec<u32> = ["alpha", "beta", "gamma"]
+            .iter()
+            .map(|s| ia.add_id(s).unwrap().to_bits())
+            .collect();
+        ia.save_to_shm(&name).unwrap();
+        drop(ia);
+        // SAFETY:
+        //
+        // The object is only replaced by save_to_shm() below.
+        let (fa1, fa2) = unsafe {
+            (FrozenArena::open_shm(&name).unwrap(), FrozenArena::open_shm_trusted(&name).unwrap())
+        };
+        // Saving again replaces the object, and the snapshots keep the old
+        // bytes.
+        let ia = IndexedStringArena::with_capacity(8);
+        ia.add("x").unwrap();
+        ia.save_to_shm(&name).unwrap();
+        // SAFETY:
+        //
+        // The object is not modified until unlinked.
+        let fa3 = unsafe { FrozenArena::open_shm(&name).unwrap() };
+        FrozenArena::unlink_shm(&name).unwrap();
+        assert_eq!(fa2.resolve(StrId::from_bits(ids[2])), "gamma");
+        assert_eq!(fa1.resolve(StrId::from_bits(ids[1])), "beta");
+        assert_eq!(fa1.iter().count(), 3);
+        assert_eq!(fa3.iter().collect::<Vec<_>>(), vec!["x"]);
+        // SAFETY:
+        //
+        // Opening fails, so nothing is mapped.
+        unsafe {
+            assert!(matches!(FrozenArena::open_shm(&name), Err(Error::Io(_))));
+            assert!(matches!(FrozenArena::open_shm("/a\0b"), Err(Error::InteriorNul(2))));
+        }
+    }
+}
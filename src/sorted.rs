@@ -0,0 +1,173 @@
+use crate::{IndexedIter, IndexedStringArena, StrId};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// SortedStringArena is a read-only dictionary of distinct strings in
+/// lexicographical order.
+///
+/// All strings are stored contiguously in sorted order, and the handle of
+/// a string is its rank, so lookup is a binary search over the offset
+/// table without any hash table.
+pub struct SortedStringArena {
+    // Strings are sorted and distinct.
+    inner: IndexedStringArena,
+}
+
+impl SortedStringArena {
+    /// Returns bytes of all stored strings.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns whether the dictionary is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns number of distinct strings stored.
+    #[inline]
+    pub fn len_strings(&self) -> usize {
+        self.inner.len_strings()
+    }
+
+    /// Returns the handle of the string if exists.
+    #[inline]
+    pub fn lookup<T: AsRef<str>>(&self, s: T) -> Option<StrId> {
+        let s = s.as_ref();
+        let (mut lo, mut hi) = (0, self.len_strings());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.inner.get(mid).unwrap().cmp(s) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Some(self.inner.id(mid)),
+            }
+        }
+        None
+    }
+
+    /// Returns an iterator over all stored strings starting with given
+    /// prefix in sorted order, e.g. for autocomplete.
+    pub fn range_by_prefix<T: AsRef<str>>(&self, prefix: T) -> impl Iterator<Item = &str> {
+        let prefix = prefix.as_ref();
+        let n = self.len_strings();
+        let lo = self.partition_point(0, n, |s| s < prefix);
+        let hi = self.partition_point(lo, n, |s| s.starts_with(prefix));
+        (lo..hi).map(|i| self.inner.get(i).unwrap())
+    }
+
+    /// Returns the index of the first string in range `lo..hi` for which
+    /// the predicate is false, given it is true for a sorted prefix.
+    #[inline]
+    fn partition_point<F: Fn(&str) -> bool>(&self, mut lo: usize, mut hi: usize, f: F) -> usize {
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if f(self.inner.get(mid).unwrap()) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Resolve the handle to string ref.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this dictionary.
+    #[inline]
+    pub fn resolve(&self, id: StrId) -> &str {
+        self.inner.resolve(id)
+    }
+
+    /// Returns the i-th string in sorted order if exists.
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<&str> {
+        self.inner.get(i)
+    }
+
+    /// Returns bytes of all stored strings, in sorted order.
+    #[inline]
+    pub fn values(&self) -> &[u8] {
+        self.inner.values()
+    }
+
+    /// Returns an iterator over all stored strings in sorted order.
+    #[inline]
+    pub fn iter(&self) -> IndexedIter<'_> {
+        self.inner.iter()
+    }
+}
+
+impl IndexedStringArena {
+    /// Build a sorted dictionary of distinct strings stored in the arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    pub fn into_sorted(self) -> SortedStringArena {
+        let mut strs: Vec<&str> = self.iter().collect();
+        strs.sort_unstable();
+        strs.dedup();
+        let cap = strs.iter().map(|s| s.len()).sum();
+        let inner = IndexedStringArena::with_capacity(cap);
+        for s in strs {
+            // Capacity is computed above, and number of strings never grows.
+            inner.add_id(s).unwrap();
+        }
+        SortedStringArena { inner }
+    }
+}
+
+impl<'a> IntoIterator for &'a SortedStringArena {
+    type Item = &'a str;
+    type IntoIter = IndexedIter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> IndexedIter<'a> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorted_string_arena() {
+        let ia = IndexedStringArena::with_capacity(64);
+        for s in ["pear", "apple", "fig", "apple", "", "banana"] {
+            ia.add(s).unwrap();
+        }
+        let sa = ia.into_sorted();
+        assert_eq!(sa.len_strings(), 5);
+        assert_eq!(sa.iter().collect::<Vec<_>>(), vec!["", "apple", "banana", "fig", "pear"]);
+        assert_eq!(sa.values(), b"applebananafigpear");
+        let id = sa.lookup("fig").unwrap();
+        assert_eq!(id.index(), 3);
+        assert_eq!(sa.resolve(id), "fig");
+        assert_eq!(sa.lookup("").map(StrId::index), Some(0));
+        assert_eq!(sa.lookup("grape"), None);
+        assert_eq!(sa.lookup("zzz"), None);
+        assert!(IndexedStringArena::with_capacity(0).into_sorted().lookup("a").is_none());
+    }
+
+    #[test]
+    fn test_range_by_prefix() {
+        let ia = IndexedStringArena::with_capacity(64);
+        for s in ["foo_bar", "fob", "foo", "bar", "foo_baz", "fop"] {
+            ia.add(s).unwrap();
+        }
+        let sa = ia.into_sorted();
+        let range = |p: &str| sa.range_by_prefix(p).collect::<Vec<_>>();
+        assert_eq!(range("foo"), vec!["foo", "foo_bar", "foo_baz"]);
+        assert_eq!(range("foo_"), vec!["foo_bar", "foo_baz"]);
+        assert_eq!(range("fo").len(), 5);
+        assert_eq!(range("").len(), 6);
+        assert!(range("fooz").is_empty());
+        assert!(range("zzz").is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{Error, Result, Span, StringArena};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ops::Range;
+
+/// FileId is a handle of a source file registered in [`SourceMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+/// Location is the position of a byte in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Location {
+    /// File containing the byte.
+    pub file: FileId,
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Column number in chars, starting from 1.
+    pub column: usize,
+}
+
+/// Source file registered in the map.
+#[derive(Debug)]
+struct File {
+    name: Span,
+    text: Span,
+    // Offsets of line starts relative to the text.
+    lines: Vec<usize>,
+}
+
+/// SourceMap is a single-thread registry of source files for compiler
+/// front ends, whose names and texts are stored in a [`StringArena`].
+///
+/// Texts of all files are laid out in one arena, so a [`Span`] of any
+/// token identifies its file by its offset alone, and can be resolved to
+/// the token text, or to file, line and column for diagnostics, without
+/// the file stored along.
+pub struct SourceMap {
+    files: UnsafeCell<Vec<File>>,
+    arena: StringArena,
+}
+
+impl SourceMap {
+    /// Create a new source map with given arena capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::try_with_capacity(cap).unwrap()
+    }
+
+    /// Try to create a new source map with given arena capacity.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity(cap: usize) -> Result<Self> {
+        Ok(SourceMap {
+            files: UnsafeCell::new(Vec::new()),
+            arena: StringArena::try_with_capacity(cap)?,
+        })
+    }
+
+    /// Returns number of files registered.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.files_ref().len()
+    }
+
+    /// Returns whether no file is registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the arena storing names and texts of files.
+    #[inline]
+    pub fn arena(&self) -> &StringArena {
+        &self.arena
+    }
+
+    /// Register a source file, adding its name and text into arena.
+    /// Returns the handle of the file if succeeds.
+    /// Nothing is kept in the arena on failure.
+    pub fn add_file<N: AsRef<str>, T: AsRef<str>>(&self, name: N, text: T) -> Result<FileId> {
+        let id = u32::try_from(self.len()).map_err(|_| Error::TooManyStrings)?;
+        let (name, text) = (name.as_ref(), text.as_ref());
+        let mut lines = vec![0];
+        lines.extend(text.bytes().zip(1..).filter(|(b, _)| *b == b'\n').map(|(_, i)| i));
+        let start = self.arena.len();
+        self.arena.push_bytes(name.as_bytes())?;
+        if let Err(e) = self.arena.push_bytes(text.as_bytes()) {
+            // SAFETY:
+            //
+            // The name pushed is not exposed.
+            unsafe { self.arena.set_len(start) };
+            return Err(e);
+        }
+        // Both are recorded only if succeeds, so nothing is counted on
+        // failure.
+        let mid = start + name.len();
+        self.arena.record_pair(start, mid);
+        let generation = self.arena.generation();
+        let name = Span::tagged(start, name.len(), generation);
+        let text = Span::tagged(mid, text.len(), generation);
+        // SAFETY:
+        //
+        // The file list is guaranteed not to be accessed concurrently,
+        // and no reference to the list escapes this method.
+        unsafe { &mut *self.files.get() }.push(File { name, text, lines });
+        Ok(FileId(id))
+    }
+
+    /// Returns the name of the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this map.
+    #[inline]
+    pub fn name(&self, file: FileId) -> &str {
+        self.arena.get(self.file(file).name).unwrap()
+    }
+
+    /// Returns the text of the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this map.
+    #[inline]
+    pub fn source(&self, file: FileId) -> &str {
+        self.arena.get(self.file(file).text).unwrap()
+    }
+
+    /// Returns the span of whole text of the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this map.
+    #[inline]
+    pub fn file_span(&self, file: FileId) -> Span {
+        self.file(file).text
+    }
+
+    /// Returns the span of given byte range of the file text.
+    /// Returns None if the range is out of the text.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this map.
+    #[inline]
+    pub fn span(&self, file: FileId, range: Range<usize>) -> Option<Span> {
+        self.file(file).text.slice(range)
+    }
+
+    /// Resolve the span to the text it covers.
+    /// Returns None if the span is not within written bytes or not at char
+    /// boundaries.
+    #[inline]
+    pub fn resolve(&self, span: Span) -> Option<&str> {
+        self.arena.get(span)
+    }
+
+    /// Returns the file containing the span.
+    /// Returns None if the span is not within text of any file.
+    pub fn lookup_file(&self, span: Span) -> Option<FileId> {
+        if span.generation() != self.arena.generation() {
+            return None;
+        }
+        let files = self.files_ref();
+        let i = files.partition_point(|f| f.text.offset() <= span.offset()).checked_sub(1)?;
+        (span.end() <= files[i].text.end()).then_some(FileId(i as u32))
+    }
+
+    /// Returns the location of the start of the span.
+    /// Returns None if the span is not within text of any file, or does
+    /// not start at a char boundary.
+    pub fn lookup(&self, span: Span) -> Option<Location> {
+        let file = self.lookup_file(span)?;
+        let f = self.file(file);
+        let offset = span.offset() - f.text.offset();
+        let line = f.lines.partition_point(|&start| start <= offset);
+        let text = self.arena.get(f.text).unwrap();
+        let column = text.get(f.lines[line - 1]..offset)?.chars().count() + 1;
+        Some(Location { file, line, column })
+    }
+
+    /// Returns the text of given line of the file, without the line break.
+    /// Returns None if the line is out of the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this map.
+    pub fn line(&self, file: FileId, line: usize) -> Option<&str> {
+        let f = self.file(file);
+        let start = *f.lines.get(line.checked_sub(1)?)?;
+        let end = f.lines.get(line).map_or(f.text.len(), |&next| next - 1);
+        let text = self.arena.get(f.text).unwrap();
+        Some(text[start..end].strip_suffix('\r').unwrap_or(&text[start..end]))
+    }
+
+    #[inline]
+    fn file(&self, file: FileId) -> &File {
+        &self.files_ref()[file.0 as usize]
+    }
+
+    #[inline]
+    fn files_ref(&self) -> &[File] {
+        // SAFETY:
+        //
+        // The file list is only mutated inside add_file(), which never
+        // runs concurrently with this shared access.
+        unsafe { &*self.files.get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map() {
+        let sm = SourceMap::with_capacity(256);
+        let main = sm.add_file("main.rs", "fn main() {\r\n    let π = 1;\n}").unwrap();
+        let lib = sm.add_file("lib.rs", "").unwrap();
+        let util = sm.add_file("util.rs", "pub fn f() {}\n").unwrap();
+        assert_eq!(sm.len(), 3);
+        assert_eq!(sm.name(util), "util.rs");
+        assert_eq!(sm.source(util), "pub fn f() {}\n");
+        let tok = sm.span(main, 24..25).unwrap();
+        assert_eq!(sm.resolve(tok), Some("="));
+        assert_eq!(sm.lookup(tok), Some(Location { file: main, line: 2, column: 11 }));
+        let f = sm.span(util, 7..8).unwrap();
+        assert_eq!(sm.lookup(f), Some(Location { file: util, line: 1, column: 8 }));
+        assert_eq!(sm.lookup(sm.file_span(lib)).map(|l| l.file), Some(lib));
+        let end = sm.span(util, 14..14).unwrap();
+        assert_eq!(sm.lookup(end), Some(Location { file: util, line: 2, column: 1 }));
+        assert_eq!(sm.lookup(sm.span(main, 22..23).unwrap()), None);
+        assert!(sm.span(main, 20..40).is_none());
+        assert_eq!(sm.lookup(Span::new(0, 3)), None);
+        assert_eq!(sm.lookup(Span::new(1000, 0)), None);
+        assert_eq!(sm.line(main, 1), Some("fn main() {"));
+        assert_eq!(sm.line(main, 3), Some("}"));
+        assert_eq!(sm.line(main, 4), None);
+        assert_eq!(sm.line(util, 2), Some(""));
+        assert_eq!(sm.line(util, 0), None);
+    }
+
+    #[test]
+    fn test_source_map_add_file_failed() {
+        let sm = SourceMap::with_capacity(16);
+        assert!(sm.add_file("main.rs", "fn main() {}").is_err());
+        assert!(sm.is_empty());
+        assert!(sm.arena().is_empty());
+        assert_eq!(sm.arena().stats().strings, 0);
+        let lib = sm.add_file("lib.rs", "mod a;").unwrap();
+        assert_eq!(sm.name(lib), "lib.rs");
+        assert_eq!(sm.source(lib), "mod a;");
+        assert_eq!(sm.arena().stats().strings, 2);
+    }
+}
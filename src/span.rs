@@ -0,0 +1,185 @@
+use crate::{BackingAlloc, Result, StringArena};
+use core::ops::{Bound, RangeBounds};
+
+/// Span is a position-based handle of a string stored in [`StringArena`].
+///
+/// It is the offset and length of the string, so it does not borrow the
+/// arena and can be copied, stored and transferred freely. Resolving it
+/// with [`StringArena::get`] checks it against the arena, including the
+/// generation it is taken in, so use after clear is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Span {
+    offset: usize,
+    len: usize,
+    generation: u32,
+}
+
+impl Span {
+    /// Create a new span with given offset and length, in the first
+    /// generation of arena.
+    #[inline]
+    pub const fn new(offset: usize, len: usize) -> Self {
+        Span::tagged(offset, len, 0)
+    }
+
+    #[inline]
+    pub(crate) const fn tagged(offset: usize, len: usize, generation: u32) -> Self {
+        Span {
+            offset,
+            len,
+            generation,
+        }
+    }
+
+    /// Returns the offset of the string in arena.
+    #[inline]
+    pub const fn offset(self) -> usize {
+        self.offset
+    }
+
+    /// Returns the length of the string.
+    #[inline]
+    pub const fn len(self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the string is empty.
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the generation of arena the span is taken in.
+    #[inline]
+    pub const fn generation(self) -> u32 {
+        self.generation
+    }
+
+    /// Returns the end offset of the string in arena.
+    #[inline]
+    pub const fn end(self) -> usize {
+        self.offset.saturating_add(self.len)
+    }
+
+    /// Returns the sub-span of given byte range relative to this span.
+    /// Returns None if the range is out of this span.
+    /// Char boundaries are checked when resolving the sub-span.
+    #[inline]
+    pub fn slice<R: RangeBounds<usize>>(self, range: R) -> Option<Span> {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.checked_add(1)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1)?,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len,
+        };
+        if start > end || end > self.len {
+            return None;
+        }
+        let offset = self.offset.checked_add(start)?;
+        Some(Span::tagged(offset, end - start, self.generation))
+    }
+}
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Add a string into current arena.
+    /// Returns the span of the string if succeeds.
+    #[inline]
+    pub fn add_span<T: AsRef<str>>(&self, s: T) -> Result<Span> {
+        let res = self.add(s)?;
+        let offset = self.len() - res.len();
+        Ok(Span::tagged(offset, res.len(), self.generation()))
+    }
+
+    /// Resolve the span to string ref.
+    /// Returns None if the span is stale, out of written bytes, overlaps
+    /// typed values, or the bytes are not valid UTF-8, e.g. it does not
+    /// start or end at char boundaries.
+    #[inline]
+    pub fn get(&self, span: Span) -> Option<&str> {
+        if span.generation != self.generation()
+            || span.end() > self.len()
+            || self.overlaps_typed(span.offset, span.end())
+        {
+            return None;
+        }
+        // SAFETY:
+        //
+        // The region is within written bytes, and not typed values which
+        // may contain uninitialized padding.
+        let bs = unsafe { self.bytes_unchecked(span.offset, span.end()) };
+        core::str::from_utf8(bs).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span() {
+        let sa = StringArena::with_capacity(16);
+        let s1 = sa.add_span("hello").unwrap();
+        let s2 = sa.add_span("世界").unwrap();
+        assert_eq!(s1, Span::new(0, 5));
+        assert_eq!(s2.offset(), 5);
+        assert_eq!(s2.len(), 6);
+        assert_eq!(sa.get(s1), Some("hello"));
+        assert_eq!(sa.get(s2), Some("世界"));
+        assert_eq!(sa.get(Span::new(1, 3)), Some("ell"));
+        assert_eq!(sa.get(Span::new(6, 2)), None);
+        assert_eq!(sa.get(Span::new(10, 2)), None);
+        assert_eq!(sa.get(Span::new(usize::MAX, 2)), None);
+        assert_eq!(sa.get(Span::default()), Some(""));
+    }
+
+    #[test]
+    fn test_span_slice() {
+        let sa = StringArena::with_capacity(16);
+        sa.add("ab").unwrap();
+        let span = sa.add_span("hello世").unwrap();
+        assert_eq!(sa.get(span.slice(1..3).unwrap()), Some("el"));
+        assert_eq!(sa.get(span.slice(..=1).unwrap()), Some("he"));
+        assert_eq!(sa.get(span.slice(5..).unwrap()), Some("世"));
+        assert_eq!(sa.get(span.slice(6..).unwrap()), None);
+        assert!(span.slice(3..9).is_none());
+        assert!(span.slice((Bound::Included(3), Bound::Excluded(2))).is_none());
+        assert!(Span::new(usize::MAX, 4).slice(1..).is_none());
+    }
+
+    #[test]
+    fn test_span_generation() {
+        let mut sa = StringArena::with_capacity(16);
+        let s1 = sa.add_span("hello").unwrap();
+        let mark = sa.checkpoint();
+        sa.truncate_to(mark);
+        assert_eq!(sa.get(s1), Some("hello"));
+        sa.add("world").unwrap();
+        sa.truncate_to(mark);
+        assert_eq!(sa.generation(), 1);
+        assert_eq!(sa.get(s1), None);
+        let s2 = sa.add_span("hello").unwrap();
+        assert_eq!(s2.generation(), 1);
+        assert_eq!(sa.get(s2.slice(1..).unwrap()), Some("ello"));
+        sa.clear();
+        assert_eq!(sa.get(s2), None);
+        assert_eq!(sa.get(Span::default()), None);
+    }
+
+    #[test]
+    fn test_span_typed() {
+        let sa = StringArena::with_capacity(32);
+        sa.add_span("ab").unwrap();
+        sa.add_value((1u8, 2u32)).unwrap();
+        let end = sa.len();
+        let s = sa.add_span("cd").unwrap();
+        assert_eq!(sa.get(Span::new(0, 2)), Some("ab"));
+        assert_eq!(sa.get(Span::new(0, end)), None);
+        assert_eq!(sa.get(Span::new(end - 1, 3)), None);
+        assert_eq!(sa.get(Span::new(2, 0)), Some(""));
+        assert_eq!(sa.get(s), Some("cd"));
+    }
+}
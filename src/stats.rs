@@ -0,0 +1,57 @@
+/// Stats is the statistics of strings added into an arena.
+///
+/// It is accumulated since the arena is created or last cleared. Strings
+/// discarded afterwards, e.g. by [`pop_last`](crate::StringArena::pop_last),
+/// are still counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Number of strings added.
+    pub strings: usize,
+    /// Total bytes of strings added.
+    pub bytes: usize,
+    /// Length of the longest string added.
+    pub longest: usize,
+    /// Maximum bytes written at the same time.
+    pub peak_len: usize,
+    /// Number of additions failed because of exceeding capacity.
+    pub failures: usize,
+    /// Total bytes of additions failed because of exceeding capacity.
+    pub bytes_rejected: usize,
+    /// Total bytes of padding inserted to align values.
+    pub wasted_bytes: usize,
+}
+
+impl Stats {
+    /// Returns average length of strings added, or zero if nothing added.
+    #[inline]
+    pub fn average_len(&self) -> f64 {
+        if self.strings == 0 {
+            0.0
+        } else {
+            self.bytes as f64 / self.strings as f64
+        }
+    }
+
+    /// Update statistics with a newly added string of given length, and
+    /// bytes written after the addition.
+    #[inline]
+    pub(crate) fn record(&mut self, len: usize, written: usize) {
+        self.strings += 1;
+        self.bytes += len;
+        self.longest = self.longest.max(len);
+        self.peak_len = self.peak_len.max(written);
+    }
+
+    /// Update statistics with a failed addition of given length.
+    #[inline]
+    pub(crate) fn reject(&mut self, len: usize) {
+        self.failures += 1;
+        self.bytes_rejected = self.bytes_rejected.saturating_add(len);
+    }
+
+    /// Update statistics with padding of given length.
+    #[inline]
+    pub(crate) fn waste(&mut self, len: usize) {
+        self.wasted_bytes += len;
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{BackingAlloc, Result, StringArena};
+use core::alloc::Layout;
+use core::ops::Deref;
+
+/// ArenaStrVec is a list of string refs stored entirely inside an arena.
+///
+/// Both the strings and the list itself are stored in the arena, so no
+/// separate heap allocation is needed, and all of them are freed with
+/// the arena at once. It is returned by [`StringArena::add_str_vec`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArenaStrVec<'a>(&'a [&'a str]);
+
+impl<'a> ArenaStrVec<'a> {
+    /// Returns the string refs as a slice.
+    #[inline]
+    pub fn as_slice(&self) -> &'a [&'a str] {
+        self.0
+    }
+}
+
+impl<'a> Deref for ArenaStrVec<'a> {
+    type Target = [&'a str];
+
+    #[inline]
+    fn deref(&self) -> &[&'a str] {
+        self.0
+    }
+}
+
+impl<'a> IntoIterator for ArenaStrVec<'a> {
+    type Item = &'a &'a str;
+    type IntoIter = core::slice::Iter<'a, &'a str>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Add all strings into current arena, together with the list of
+    /// their refs.
+    /// Returns the list if succeeds.
+    /// Nothing is kept in the arena if it exceeds capacity.
+    pub fn add_str_vec<T: AsRef<str>>(&self, strs: &[T]) -> Result<ArenaStrVec<'_>> {
+        let start = self.len();
+        let layout = Layout::array::<&str>(strs.len()).unwrap();
+        let list = self.reserve_layout(layout)?.as_ptr() as *mut &str;
+        self.mark_typed(start, self.len());
+        for (i, s) in strs.iter().enumerate() {
+            match self.push_bytes(s.as_ref().as_bytes()) {
+                // SAFETY:
+                //
+                // The list is reserved above and properly aligned, and
+                // the bytes are copied from valid UTF-8.
+                Ok(bs) => unsafe { list.add(i).write(core::str::from_utf8_unchecked(bs)) },
+                Err(e) => {
+                    // SAFETY:
+                    //
+                    // Nothing added above is exposed.
+                    unsafe { self.set_len(start) };
+                    return Err(e);
+                }
+            }
+        }
+        self.record(start);
+        // SAFETY:
+        //
+        // All elements of the list are initialized above.
+        Ok(ArenaStrVec(unsafe { core::slice::from_raw_parts(list, strs.len()) }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena_str_vec() {
+        let sa = StringArena::with_capacity(64);
+        sa.add("x").unwrap();
+        let v = sa.add_str_vec(&["foo", "", "bar"]).unwrap();
+        assert_eq!(v.len(), 3);
+        assert_eq!(v.as_slice(), &["foo", "", "bar"]);
+        assert_eq!(v.into_iter().copied().collect::<Vec<_>>(), vec!["foo", "", "bar"]);
+        let len = sa.len();
+        assert!(sa.add_str_vec(&["a".repeat(20)]).is_err());
+        assert_eq!(sa.len(), len);
+        assert!(sa.add_str_vec::<&str>(&[]).unwrap().is_empty());
+    }
+}
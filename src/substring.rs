@@ -0,0 +1,123 @@
+use crate::{IndexedStringArena, StrId};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// SubstringIndex is a suffix array over strings of an
+/// [`IndexedStringArena`], which finds all strings containing a fragment
+/// without scanning them.
+///
+/// It covers strings stored when it is built. Strings added afterwards
+/// are not searched.
+pub struct SubstringIndex<'a> {
+    arena: &'a IndexedStringArena,
+    // Start of every suffix of the indexed bytes, in sorted order.
+    suffixes: Vec<usize>,
+    strings: usize,
+}
+
+impl SubstringIndex<'_> {
+    /// Returns number of strings indexed.
+    #[inline]
+    pub fn len_strings(&self) -> usize {
+        self.strings
+    }
+
+    /// Returns handles of all indexed strings containing given fragment,
+    /// in insertion order. Every string contains the empty fragment.
+    pub fn find_containing<T: AsRef<str>>(&self, needle: T) -> impl Iterator<Item = StrId> + '_ {
+        let needle = needle.as_ref().as_bytes();
+        let values = &self.arena.values()[..self.suffixes.len()];
+        let offsets = &self.arena.offsets_ref()[..=self.strings];
+        let mut ids: Vec<usize> = if needle.is_empty() {
+            (0..self.strings).collect()
+        } else {
+            let prefix = |p: usize| &values[p..values.len().min(p + needle.len())];
+            let lo = self.suffixes.partition_point(|&p| prefix(p) < needle);
+            let hi = lo + self.suffixes[lo..].partition_point(|&p| prefix(p) == needle);
+            self.suffixes[lo..hi]
+                .iter()
+                .filter_map(|&p| {
+                    // The last string starting at or before the match, since
+                    // empty strings share offsets with the following one.
+                    let i = offsets.partition_point(|&o| o <= p) - 1;
+                    (p + needle.len() <= offsets[i + 1]).then_some(i)
+                })
+                .collect()
+        };
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter().map(|i| self.arena.id(i))
+    }
+}
+
+impl IndexedStringArena {
+    /// Build a substring index over strings currently stored, by prefix
+    /// doubling in `O(n log² n)` time for `n` bytes stored.
+    pub fn build_substring_index(&self) -> SubstringIndex<'_> {
+        let values = self.values();
+        let n = values.len();
+        let mut suffixes: Vec<usize> = (0..n).collect();
+        let mut rank: Vec<usize> = values.iter().map(|&b| b as usize).collect();
+        let mut next = vec![0; n];
+        let mut k = 1;
+        while k < n {
+            // Sort by rank of the first k bytes, then of the following k.
+            let key = |i: usize| (rank[i], rank.get(i + k).map_or(0, |r| r + 1));
+            suffixes.sort_unstable_by_key(|&i| key(i));
+            next[suffixes[0]] = 0;
+            for w in 1..n {
+                let (prev, curr) = (suffixes[w - 1], suffixes[w]);
+                next[curr] = next[prev] + usize::from(key(prev) != key(curr));
+            }
+            core::mem::swap(&mut rank, &mut next);
+            if rank[suffixes[n - 1]] == n - 1 {
+                break;
+            }
+            k *= 2;
+        }
+        SubstringIndex { arena: self, suffixes, strings: self.len_strings() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substring_index() {
+        let ia = IndexedStringArena::with_capacity(256);
+        let msgs = [
+            "disk full",
+            "",
+            "connection reset",
+            "disk ok",
+            "reset by peer",
+            "中文日志",
+        ];
+        let ids: Vec<StrId> = msgs.iter().map(|s| ia.add_id(s).unwrap()).collect();
+        let idx = ia.build_substring_index();
+        ia.add("late reset").unwrap();
+        assert_eq!(idx.len_strings(), 6);
+        let find = |s: &str| idx.find_containing(s).collect::<Vec<_>>();
+        assert_eq!(find("disk"), vec![ids[0], ids[3]]);
+        assert_eq!(find("reset"), vec![ids[2], ids[4]]);
+        assert_eq!(find("s"), vec![ids[0], ids[2], ids[3], ids[4]]);
+        assert_eq!(find("日志"), vec![ids[5]]);
+        assert_eq!(find("fullconn"), vec![]);
+        assert_eq!(find("okreset"), vec![]);
+        assert_eq!(find("late"), vec![]);
+        assert_eq!(find("").len(), 6);
+        let empty = IndexedStringArena::with_capacity(0);
+        assert_eq!(empty.build_substring_index().find_containing("a").count(), 0);
+    }
+
+    #[test]
+    fn test_suffix_order() {
+        let ia = IndexedStringArena::with_capacity(64);
+        ia.add("banana").unwrap();
+        ia.add("abracadabra").unwrap();
+        let idx = ia.build_substring_index();
+        let values = ia.values();
+        assert!(idx.suffixes.windows(2).all(|w| values[w[0]..] < values[w[1]..]));
+    }
+}
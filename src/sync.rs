@@ -0,0 +1,270 @@
+use crate::{Error, Result};
+use alloc::alloc::{alloc, dealloc, Layout};
+use core::mem::align_of;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Source of identities of arenas, which are never reused.
+static NEXT_ARENA_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// SyncStrId is an owned handle of a string stored in [`SyncStringArena`].
+///
+/// It does not borrow the arena, so producers can send it to consumers
+/// which resolve it against the shared arena. It can only be created by
+/// the arena, and is resolved only by the arena which returned it, before
+/// the arena is cleared.
+///
+/// Offset and length are packed in `u32`s, so handles address the first
+/// 4 GiB of the arena. The identity of the arena is kept in full rather
+/// than a few bits, because a stale handle resolved by mistake would read
+/// bytes other threads may be writing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncStrId {
+    arena: usize,
+    offset: u32,
+    len: u32,
+}
+
+/// SyncStringArena is a thread-safe append-only string arena.
+///
+/// Multiple threads can add strings concurrently. Space is reserved
+/// by an atomic bump of the index, so each thread writes into a
+/// disjoint region and the returned string refs can be shared freely.
+pub struct SyncStringArena {
+    ptr: NonNull<u8>,
+    cap: usize,
+    idx: AtomicUsize,
+    // Identity of current generation, renewed on clear.
+    id: usize,
+}
+
+// SAFETY:
+//
+// The buffer is owned by the arena and every written region is only
+// written once by the thread which reserved it.
+unsafe impl Send for SyncStringArena {}
+unsafe impl Sync for SyncStringArena {}
+
+impl SyncStringArena {
+    /// Create a new thread-safe string arena with given capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::try_with_capacity(cap).unwrap()
+    }
+
+    /// Try to create a new thread-safe string arena with given capacity.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity(cap: usize) -> Result<Self> {
+        let ptr = if cap == 0 {
+            NonNull::dangling()
+        } else {
+            let layout = Layout::from_size_align(cap, align_of::<u8>())
+                .map_err(|_| Error::AllocFailed(cap))?;
+            // SAFETY:
+            //
+            // The layout has non-zero size.
+            NonNull::new(unsafe { alloc(layout) }).ok_or(Error::AllocFailed(cap))?
+        };
+        Ok(SyncStringArena {
+            ptr,
+            cap,
+            idx: AtomicUsize::new(0),
+            id: NEXT_ARENA_ID.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    /// Returns bytes reserved of current arena.
+    /// The value may be stale if other threads are adding strings.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.idx.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the arena is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns capacity of current arena.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Add a string into current arena.
+    /// Returns the string ref if succeeds.
+    /// The only reason of failure is that input string exceeds remained capacity.
+    /// The additional bytes required to store it is returned if fails.
+    #[inline]
+    pub fn add<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        self.add_within(s.as_ref(), self.cap)
+    }
+
+    /// Add a string into current arena, ending no further than the limit.
+    #[inline]
+    fn add_within(&self, s: &str, limit: usize) -> Result<&str> {
+        let len = s.len();
+        let mut idx = self.idx.load(Ordering::Relaxed);
+        loop {
+            let new_len = idx + len;
+            if limit < new_len {
+                return Err(Error::ExceedsCapacity(new_len - limit));
+            }
+            match self.idx.compare_exchange_weak(
+                idx,
+                new_len,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(curr) => idx = curr,
+            }
+        }
+        // SAFETY:
+        //
+        // The region [idx, idx + len) is exclusively reserved by the CAS above,
+        // so no other thread reads or writes it concurrently.
+        unsafe {
+            let dst = self.ptr.as_ptr().add(idx);
+            core::ptr::copy_nonoverlapping(s.as_ptr(), dst, len);
+            let bs = core::slice::from_raw_parts(dst, len);
+            Ok(core::str::from_utf8_unchecked(bs))
+        }
+    }
+
+    /// Add a string into current arena.
+    /// Returns the handle of the string if succeeds.
+    /// Fails if the string exceeds remained capacity, or ends beyond 4 GiB
+    /// which handles cannot address.
+    #[inline]
+    pub fn add_id<T: AsRef<str>>(&self, s: T) -> Result<SyncStrId> {
+        let res = self.add_within(s.as_ref(), self.cap.min(u32::MAX as usize))?;
+        Ok(SyncStrId {
+            arena: self.id,
+            offset: (res.as_ptr() as usize - self.ptr.as_ptr() as usize) as u32,
+            len: res.len() as u32,
+        })
+    }
+
+    /// Resolve the handle to string ref.
+    /// Returns None if the handle is not returned by this arena, or the
+    /// arena is cleared afterwards.
+    #[inline]
+    pub fn resolve(&self, id: SyncStrId) -> Option<&str> {
+        if id.arena != self.id {
+            return None;
+        }
+        // SAFETY:
+        //
+        // The handle is only created after the string is written, and it
+        // must be transferred to current thread with synchronization, so
+        // the write happens before this read.
+        unsafe {
+            let dst = self.ptr.as_ptr().add(id.offset as usize);
+            let bs = core::slice::from_raw_parts(dst, id.len as usize);
+            Some(core::str::from_utf8_unchecked(bs))
+        }
+    }
+
+    /// Clear the arena so its memory can be reused.
+    /// The exclusive borrow guarantees no string ref of this arena is alive.
+    /// Handles returned before are invalidated.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self.idx.get_mut() = 0;
+        self.id = NEXT_ARENA_ID.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renew the arena for future usage.
+    /// This is safe because all string refs associated to this arena
+    /// must be dropped before this method call.
+    #[inline]
+    pub fn renew(mut self) -> Self {
+        self.clear();
+        self
+    }
+}
+
+impl Drop for SyncStringArena {
+    #[inline]
+    fn drop(&mut self) {
+        if self.cap != 0 {
+            // SAFETY:
+            //
+            // The pointer is allocated with the same layout in constructor.
+            unsafe {
+                let layout = Layout::from_size_align_unchecked(self.cap, align_of::<u8>());
+                dealloc(self.ptr.as_ptr(), layout);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_string_arena() {
+        let sa = SyncStringArena::with_capacity(4000);
+        let res: Vec<Vec<&str>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|i| {
+                    let sa = &sa;
+                    s.spawn(move || {
+                        (0..100)
+                            .map(|j| sa.add(format!("{}-{:06}", i, j)).unwrap())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for (i, strs) in res.iter().enumerate() {
+            for (j, s) in strs.iter().enumerate() {
+                assert_eq!(*s, format!("{}-{:06}", i, j));
+            }
+        }
+        assert_eq!(sa.len(), 3200);
+        assert!(sa.add("x".repeat(801)).is_err());
+        let sa2 = sa.renew();
+        assert!(sa2.is_empty());
+    }
+
+    #[test]
+    fn test_sync_str_id() {
+        use std::sync::mpsc;
+
+        let mut sa = SyncStringArena::with_capacity(1024);
+        let (tx, rx) = mpsc::channel();
+        let res: Vec<String> = std::thread::scope(|s| {
+            for i in 0..4 {
+                let (sa, tx) = (&sa, tx.clone());
+                s.spawn(move || {
+                    for j in 0..10 {
+                        tx.send(sa.add_id(format!("{}-{}", i, j)).unwrap()).unwrap();
+                    }
+                });
+            }
+            drop(tx);
+            let sa = &sa;
+            s.spawn(move || rx.iter().map(|id| sa.resolve(id).unwrap().to_string()).collect())
+                .join()
+                .unwrap()
+        });
+        assert_eq!(res.len(), 40);
+        assert!(res.contains(&"3-9".to_string()));
+        let id = sa.add_id("hello").unwrap();
+        assert!(SyncStringArena::with_capacity(1024).resolve(id).is_none());
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(core::mem::size_of::<SyncStrId>(), 16);
+        sa.clear();
+        assert!(sa.resolve(id).is_none());
+    }
+}
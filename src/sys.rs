@@ -0,0 +1,79 @@
+//! Minimal bindings of OS memory APIs used by optional features.
+#![allow(non_camel_case_types)]
+
+#[cfg(feature = "std")]
+use core::ffi::c_char;
+use core::ffi::{c_int, c_long, c_ulong, c_void};
+
+pub(crate) const PROT_NONE: c_int = 0;
+pub(crate) const PROT_READ: c_int = 1;
+pub(crate) const PROT_WRITE: c_int = 2;
+pub(crate) const MAP_PRIVATE: c_int = 2;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) const MAP_ANONYMOUS: c_int = 0x20;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) const MAP_ANONYMOUS: c_int = 0x1000;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) const MAP_NORESERVE: c_int = 0x4000;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) const MAP_HUGETLB: c_int = 0x40000;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) const MADV_HUGEPAGE: c_int = 14;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub(crate) const SYS_MBIND: c_long = 237;
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+pub(crate) const SYS_MBIND: c_long = 235;
+#[cfg(target_os = "linux")]
+pub(crate) const MPOL_BIND: c_int = 2;
+#[cfg(target_os = "linux")]
+pub(crate) const MPOL_INTERLEAVE: c_int = 3;
+pub(crate) const MADV_DONTNEED: c_int = 4;
+#[cfg(feature = "std")]
+pub(crate) const O_RDONLY: c_int = 0;
+#[cfg(feature = "std")]
+pub(crate) const O_RDWR: c_int = 2;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub(crate) const O_CREAT: c_int = 0o100;
+#[cfg(all(feature = "std", not(any(target_os = "linux", target_os = "android"))))]
+pub(crate) const O_CREAT: c_int = 0x200;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub(crate) const O_EXCL: c_int = 0o200;
+#[cfg(all(feature = "std", not(any(target_os = "linux", target_os = "android"))))]
+pub(crate) const O_EXCL: c_int = 0x800;
+pub(crate) const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+pub(crate) type off_t = c_long;
+
+extern "C" {
+    pub(crate) fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: off_t,
+    ) -> *mut c_void;
+    pub(crate) fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    pub(crate) fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    pub(crate) fn madvise(addr: *mut c_void, len: usize, advice: c_int) -> c_int;
+    #[cfg(feature = "mlock")]
+    pub(crate) fn mlock(addr: *const c_void, len: usize) -> c_int;
+    #[cfg(feature = "mlock")]
+    pub(crate) fn munlock(addr: *const c_void, len: usize) -> c_int;
+    #[cfg(target_os = "linux")]
+    pub(crate) fn syscall(num: c_long, ...) -> c_long;
+    #[cfg(feature = "std")]
+    pub(crate) fn shm_open(name: *const c_char, oflag: c_int, ...) -> c_int;
+    #[cfg(feature = "std")]
+    pub(crate) fn shm_unlink(name: *const c_char) -> c_int;
+}
+
+/// Set NUMA memory policy of given range, with a node mask of the first
+/// 64 nodes.
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[inline]
+pub(crate) unsafe fn mbind(addr: *mut c_void, len: usize, mode: c_int, mask: u64) -> c_long {
+    // The kernel expects one more than number of bits in the mask.
+    let maxnode: c_ulong = 65;
+    syscall(SYS_MBIND, addr, len, mode as c_long, &mask as *const u64, maxnode, 0 as c_ulong)
+}
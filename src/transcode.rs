@@ -0,0 +1,220 @@
+//! Adding strings transcoded from other encodings or converted during
+//! the copy, without intermediate allocation.
+use crate::{BackingAlloc, Error, Result, StringArena};
+use core::alloc::Layout;
+use core::fmt::Write;
+
+impl<A: BackingAlloc> StringArena<A> {
+    /// Copy bytes into current arena, validating they are UTF-8.
+    /// Returns the string ref if succeeds.
+    /// Fails with length of the valid prefix if input is invalid, and
+    /// nothing is kept in the arena.
+    #[inline]
+    pub fn add_utf8<T: AsRef<[u8]>>(&self, bs: T) -> Result<&str> {
+        let idx = self.len();
+        // Validate the copy, which is hot in cache right after copying.
+        let copied = self.push_bytes(bs.as_ref())?;
+        match core::str::from_utf8(copied) {
+            Ok(s) => {
+                self.record(idx);
+                Ok(s)
+            }
+            Err(e) => {
+                // SAFETY:
+                //
+                // The copied bytes are not exposed.
+                unsafe { self.set_len(idx) };
+                Err(Error::InvalidUtf8(e.valid_up_to()))
+            }
+        }
+    }
+
+    /// Decode UTF-16 directly into current arena.
+    /// Returns the string ref if succeeds.
+    /// Fails with position of the first unpaired surrogate if input is
+    /// invalid, and nothing is kept in the arena.
+    #[inline]
+    pub fn add_from_utf16(&self, units: &[u16]) -> Result<&str> {
+        let mut w = self.writer();
+        let mut pos = 0;
+        for c in char::decode_utf16(units.iter().copied()) {
+            match c {
+                Ok(c) => {
+                    if w.write_char(c).is_err() {
+                        break;
+                    }
+                    pos += c.len_utf16();
+                }
+                Err(_) => {
+                    w.fail(Error::InvalidUtf16(pos));
+                    break;
+                }
+            }
+        }
+        w.finish()
+    }
+
+    /// Decode UTF-16 directly into current arena, replacing unpaired
+    /// surrogates with [`char::REPLACEMENT_CHARACTER`].
+    /// Returns the string ref if succeeds.
+    #[inline]
+    pub fn add_from_utf16_lossy(&self, units: &[u16]) -> Result<&str> {
+        let mut w = self.writer();
+        for c in char::decode_utf16(units.iter().copied()) {
+            if w.write_char(c.unwrap_or(char::REPLACEMENT_CHARACTER)).is_err() {
+                break;
+            }
+        }
+        w.finish()
+    }
+
+    /// Decode Latin-1 (ISO-8859-1) bytes directly into current arena.
+    /// Returns the string ref if succeeds.
+    #[inline]
+    pub fn add_from_latin1(&self, bs: &[u8]) -> Result<&str> {
+        if bs.is_ascii() {
+            // SAFETY:
+            //
+            // ASCII is valid UTF-8.
+            return self.add(unsafe { core::str::from_utf8_unchecked(bs) });
+        }
+        let mut w = self.writer();
+        for &b in bs {
+            if w.write_char(b as char).is_err() {
+                break;
+            }
+        }
+        w.finish()
+    }
+
+    /// Copy bytes into current arena, replacing invalid UTF-8 sequences
+    /// with [`char::REPLACEMENT_CHARACTER`].
+    /// Returns the string ref if succeeds.
+    #[inline]
+    pub fn add_lossy(&self, bs: &[u8]) -> Result<&str> {
+        if let Ok(s) = core::str::from_utf8(bs) {
+            return self.add(s);
+        }
+        let mut w = self.writer();
+        for chunk in bs.utf8_chunks() {
+            if w.write_str(chunk.valid()).is_err() {
+                break;
+            }
+            if !chunk.invalid().is_empty() && w.write_char(char::REPLACEMENT_CHARACTER).is_err() {
+                break;
+            }
+        }
+        w.finish()
+    }
+
+    /// Copy a string into current arena, converting it to Unicode lowercase.
+    /// Returns the converted string ref if succeeds.
+    /// The conversion is per char, so context-dependent mappings like
+    /// final sigma are not applied.
+    #[inline]
+    pub fn add_lowercase<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        let s = s.as_ref();
+        if s.is_ascii() {
+            return self.add_ascii_mapped(s, <[u8]>::make_ascii_lowercase);
+        }
+        let mut w = self.writer();
+        for c in s.chars().flat_map(char::to_lowercase) {
+            if w.write_char(c).is_err() {
+                break;
+            }
+        }
+        w.finish()
+    }
+
+    /// Copy a string into current arena, converting it to Unicode uppercase.
+    /// Returns the converted string ref if succeeds.
+    #[inline]
+    pub fn add_uppercase<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        let s = s.as_ref();
+        if s.is_ascii() {
+            return self.add_ascii_mapped(s, <[u8]>::make_ascii_uppercase);
+        }
+        let mut w = self.writer();
+        for c in s.chars().flat_map(char::to_uppercase) {
+            if w.write_char(c).is_err() {
+                break;
+            }
+        }
+        w.finish()
+    }
+
+    /// Copy a string into current arena, converting ASCII letters to
+    /// lowercase and leaving other chars unchanged.
+    /// Returns the converted string ref if succeeds.
+    #[inline]
+    pub fn add_ascii_folded<T: AsRef<str>>(&self, s: T) -> Result<&str> {
+        self.add_ascii_mapped(s.as_ref(), <[u8]>::make_ascii_lowercase)
+    }
+
+    /// Copy a string and apply an ASCII-only mapping in place.
+    #[inline]
+    fn add_ascii_mapped(&self, s: &str, f: fn(&mut [u8])) -> Result<&str> {
+        let len = s.len();
+        let dst = self.alloc_layout(Layout::array::<u8>(len).unwrap())?.as_ptr();
+        // SAFETY:
+        //
+        // The destination is reserved for the string, and mapping ASCII
+        // bytes keeps it valid UTF-8.
+        unsafe {
+            core::ptr::copy_nonoverlapping(s.as_ptr(), dst, len);
+            let bs = core::slice::from_raw_parts_mut(dst, len);
+            f(bs);
+            Ok(core::str::from_utf8_unchecked(bs))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, StringArena};
+
+    #[test]
+    fn test_add_utf8() {
+        let sa = StringArena::with_capacity(16);
+        assert_eq!(sa.add_utf8(b"hello").unwrap(), "hello");
+        assert_eq!(sa.add_utf8("é".as_bytes()).unwrap(), "é");
+        assert!(matches!(sa.add_utf8(b"ab\xffc"), Err(Error::InvalidUtf8(2))));
+        assert_eq!(sa.len(), 7);
+        assert!(matches!(sa.add_utf8([b'a'; 10]), Err(Error::ExceedsCapacity(1))));
+    }
+
+    #[test]
+    fn test_add_from_utf16() {
+        let sa = StringArena::with_capacity(32);
+        let units: Vec<u16> = "héllo𝄞".encode_utf16().collect();
+        assert_eq!(sa.add_from_utf16(&units).unwrap(), "héllo𝄞");
+        let len = sa.len();
+        let invalid = [0x61, 0xd834, 0x62];
+        assert!(matches!(sa.add_from_utf16(&invalid), Err(Error::InvalidUtf16(1))));
+        assert_eq!(sa.len(), len);
+        assert_eq!(sa.add_from_utf16_lossy(&invalid).unwrap(), "a\u{fffd}b");
+        assert!(sa.add_from_utf16(&[0x61; 32]).is_err());
+    }
+
+    #[test]
+    fn test_add_case_converted() {
+        let sa = StringArena::with_capacity(64);
+        assert_eq!(sa.add_lowercase("Hello").unwrap(), "hello");
+        assert_eq!(sa.add_lowercase("ÀÉÎ").unwrap(), "àéî");
+        assert_eq!(sa.add_uppercase("straße").unwrap(), "STRASSE");
+        assert_eq!(sa.add_ascii_folded("ÀbC").unwrap(), "Àbc");
+        assert!(sa.add_lowercase("X".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_add_from_latin1_and_lossy() {
+        let sa = StringArena::with_capacity(32);
+        assert_eq!(sa.add_from_latin1(b"abc").unwrap(), "abc");
+        assert_eq!(sa.add_from_latin1(b"caf\xe9").unwrap(), "café");
+        assert_eq!(sa.add_lossy(b"ok").unwrap(), "ok");
+        assert_eq!(sa.add_lossy(b"a\xffb\xe2\x82").unwrap(), "a\u{fffd}b\u{fffd}");
+        let len = sa.len();
+        assert!(sa.add_lossy(&[0xff; 8]).is_err());
+        assert_eq!(sa.len(), len);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{IndexedStringArena, Result, StrId};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+
+/// Link to no node, or no string of a node.
+const NONE: u32 = u32::MAX;
+
+/// Node of the radix tree, whose edge label is a range of arena bytes.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    off: usize,
+    len: usize,
+    child: u32,
+    sibling: u32,
+    value: u32,
+}
+
+impl Node {
+    const ROOT: Node = Node { off: 0, len: 0, child: NONE, sibling: NONE, value: NONE };
+}
+
+/// TrieInterner is a single-thread string interner indexed by a radix
+/// tree instead of a hash table.
+///
+/// Edge labels of the tree are ranges of bytes already stored in the
+/// [`IndexedStringArena`], and nodes link to each other by position in a
+/// flat node list, so the index contains no pointers nor copies of
+/// strings, and stays valid wherever the arena bytes are relocated.
+///
+/// The node list is a separate heap allocation rather than part of the
+/// arena, because nodes are split in place on insertion, while the arena
+/// has fixed capacity and its bytes are immutable once written. So the
+/// interner is not a single persistable blob: only its arena can be saved,
+/// e.g. by `IndexedStringArena::save_to`, and the tree is rebuilt by
+/// interning the strings again.
+pub struct TrieInterner {
+    nodes: UnsafeCell<Vec<Node>>,
+    arena: IndexedStringArena,
+}
+
+impl TrieInterner {
+    /// Create a new trie interner with given arena capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocation fails.
+    #[inline]
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::try_with_capacity(cap).unwrap()
+    }
+
+    /// Try to create a new trie interner with given arena capacity.
+    /// Returns error if the allocation fails.
+    #[inline]
+    pub fn try_with_capacity(cap: usize) -> Result<Self> {
+        Ok(TrieInterner {
+            nodes: UnsafeCell::new(vec![Node::ROOT]),
+            arena: IndexedStringArena::try_with_capacity(cap)?,
+        })
+    }
+
+    /// Returns number of distinct strings interned.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.arena.len_strings()
+    }
+
+    /// Returns whether the interner is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns number of nodes of the tree, including the root.
+    #[inline]
+    pub fn len_nodes(&self) -> usize {
+        self.nodes_ref().len()
+    }
+
+    /// Returns the arena storing interned strings.
+    #[inline]
+    pub fn arena(&self) -> &IndexedStringArena {
+        &self.arena
+    }
+
+    /// Returns the handle of the string if interned.
+    pub fn lookup<T: AsRef<str>>(&self, s: T) -> Option<StrId> {
+        let (nodes, values) = (self.nodes_ref(), self.arena.values());
+        let mut rest = s.as_ref().as_bytes();
+        let mut node = 0;
+        while let Some(&b) = rest.first() {
+            let c = find_child(nodes, values, node, b)?;
+            let label = &values[nodes[c].off..nodes[c].off + nodes[c].len];
+            rest = rest.strip_prefix(label)?;
+            node = c;
+        }
+        let value = nodes[node].value;
+        (value != NONE).then(|| self.arena.id(value as usize))
+    }
+
+    /// Returns the handle of the interned string, adding it into arena
+    /// if not exists.
+    /// Fails only if the arena exceeds capacity.
+    pub fn get_or_intern<T: AsRef<str>>(&self, s: T) -> Result<StrId> {
+        let s = s.as_ref();
+        // SAFETY:
+        //
+        // The node list is guaranteed not to be accessed concurrently,
+        // and no reference to the list escapes this method.
+        let nodes = unsafe { &mut *self.nodes.get() };
+        let values = self.arena.values();
+        let mut rest = s.as_bytes();
+        let mut node = 0;
+        while let Some(&b) = rest.first() {
+            let Some(c) = find_child(nodes, values, node, b) else {
+                break;
+            };
+            let Node { off, len, .. } = nodes[c];
+            let common = values[off..off + len]
+                .iter()
+                .zip(rest)
+                .take_while(|(a, b)| a == b)
+                .count();
+            if common < len {
+                // Split the edge in place, so links to the node are kept,
+                // which does not change strings indexed.
+                let tail = Node { off: off + common, len: len - common, sibling: NONE, ..nodes[c] };
+                nodes.push(tail);
+                let child = nodes.len() as u32 - 1;
+                nodes[c] = Node { len: common, child, value: NONE, ..nodes[c] };
+            }
+            rest = &rest[common..];
+            node = c;
+        }
+        if rest.is_empty() && nodes[node].value != NONE {
+            return Ok(self.arena.id(nodes[node].value as usize));
+        }
+        let id = self.arena.add_id(s)?;
+        let value = id.index() as u32;
+        if rest.is_empty() {
+            nodes[node].value = value;
+        } else {
+            let off = self.arena.offsets_ref()[id.index()] + s.len() - rest.len();
+            let sibling = nodes[node].child;
+            nodes.push(Node { off, len: rest.len(), child: NONE, sibling, value });
+            nodes[node].child = nodes.len() as u32 - 1;
+        }
+        Ok(id)
+    }
+
+    /// Split the text by chars matching the predicate, and intern all
+    /// non-empty tokens in one pass.
+    /// Returns handles of the tokens in order.
+    /// Fails only if the arena exceeds capacity, and tokens interned before
+    /// the failure are kept.
+    pub fn intern_split<P: FnMut(char) -> bool>(&self, text: &str, pat: P) -> Result<Vec<StrId>> {
+        text.split(pat)
+            .filter(|t| !t.is_empty())
+            .map(|t| self.get_or_intern(t))
+            .collect()
+    }
+
+    /// Resolve the handle to string ref.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is not returned by this interner.
+    #[inline]
+    pub fn resolve(&self, id: StrId) -> &str {
+        self.arena.resolve(id)
+    }
+
+    /// Clear the interner so its memory can be reused.
+    /// Handles returned before are invalidated.
+    #[inline]
+    pub fn clear(&mut self) {
+        let nodes = self.nodes.get_mut();
+        nodes.clear();
+        nodes.push(Node::ROOT);
+        self.arena.clear();
+    }
+
+    /// Renew the interner for future usage.
+    #[inline]
+    pub fn renew(mut self) -> Self {
+        self.clear();
+        self
+    }
+
+    #[inline]
+    fn nodes_ref(&self) -> &[Node] {
+        // SAFETY:
+        //
+        // The node list is only mutated inside get_or_intern(), which never
+        // runs concurrently with this shared access.
+        unsafe { &*self.nodes.get() }
+    }
+}
+
+/// Returns the child of given node whose label starts with given byte.
+#[inline]
+fn find_child(nodes: &[Node], values: &[u8], node: usize, b: u8) -> Option<usize> {
+    let mut c = nodes[node].child;
+    while c != NONE {
+        let n = &nodes[c as usize];
+        if values[n.off] == b {
+            return Some(c as usize);
+        }
+        c = n.sibling;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trie_interner() {
+        let ti = TrieInterner::with_capacity(256);
+        assert!(ti.is_empty());
+        let words = ["romane", "romanus", "romulus", "rubens", "ruber", "rubicon", "", "rom"];
+        let ids: Vec<StrId> = words.iter().map(|w| ti.get_or_intern(w).unwrap()).collect();
+        assert_eq!(ti.len(), 8);
+        for (w, &id) in words.iter().zip(&ids) {
+            assert_eq!(ti.get_or_intern(w).unwrap(), id);
+            assert_eq!(ti.lookup(w), Some(id));
+            assert_eq!(ti.resolve(id), *w);
+        }
+        assert_eq!(ti.len(), 8);
+        assert_eq!(ti.arena().len(), words.iter().map(|w| w.len()).sum::<usize>());
+        for w in ["r", "roma", "romanes", "rubicons", "x"] {
+            assert_eq!(ti.lookup(w), None);
+        }
+        assert_eq!(ti.get_or_intern("中文").unwrap().index(), 8);
+        assert_eq!(ti.lookup("中国"), None);
+        assert!(ti.get_or_intern("x".repeat(256)).is_err());
+        assert_eq!(ti.lookup("x"), None);
+        let mut ti = ti;
+        ti.clear();
+        assert_eq!(ti.len_nodes(), 1);
+        assert_eq!(ti.lookup("rom"), None);
+        let ids = ti.intern_split("to be, or not to be", |c| c == ' ' || c == ',').unwrap();
+        assert_eq!(ids.len(), 6);
+        assert_eq!(ti.len(), 4);
+        assert_eq!((ids[0], ids[1]), (ids[4], ids[5]));
+        assert_eq!(ti.resolve(ids[3]), "not");
+        assert!(ti.intern_split("", char::is_whitespace).unwrap().is_empty());
+    }
+}
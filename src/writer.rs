@@ -0,0 +1,263 @@
+use crate::{BackingAlloc, Error, Global, Result, StringArena};
+use core::fmt;
+
+/// Region is the contiguous bytes being written at the end of an arena.
+///
+/// If the arena is appended by others in the middle, written part is
+/// moved to the end to keep the region contiguous. If it is dropped
+/// without commit, written bytes are discarded when possible.
+struct Region<'a, A: BackingAlloc> {
+    arena: &'a StringArena<A>,
+    start: usize,
+    end: usize,
+    committed: bool,
+}
+
+impl<'a, A: BackingAlloc> Region<'a, A> {
+    #[inline]
+    fn new(arena: &'a StringArena<A>) -> Self {
+        let idx = arena.len();
+        Region {
+            arena,
+            start: idx,
+            end: idx,
+            committed: false,
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[inline]
+    fn write(&mut self, bs: &[u8]) -> Result<()> {
+        if self.arena.len() != self.end {
+            // SAFETY:
+            //
+            // The region is written by this writer.
+            let prev = unsafe { self.arena.bytes_unchecked(self.start, self.end) };
+            let res = self.arena.push_bytes(prev)?;
+            self.start = self.arena.len() - res.len();
+            self.end = self.arena.len();
+        }
+        self.arena.push_bytes(bs)?;
+        self.end = self.arena.len();
+        Ok(())
+    }
+
+    #[inline]
+    fn commit(mut self) -> &'a [u8] {
+        self.committed = true;
+        self.arena.record(self.start);
+        // SAFETY:
+        //
+        // The region is written by this writer.
+        unsafe { self.arena.bytes_unchecked(self.start, self.end) }
+    }
+}
+
+impl<A: BackingAlloc> Drop for Region<'_, A> {
+    #[inline]
+    fn drop(&mut self) {
+        if !self.committed && self.arena.len() == self.end {
+            // SAFETY:
+            //
+            // Bytes written by this writer are never exposed.
+            unsafe { self.arena.set_len(self.start) }
+        }
+    }
+}
+
+/// ArenaWriter formats a string directly into [`StringArena`].
+///
+/// It is returned by [`StringArena::writer`]. All pieces written are
+/// stored contiguously, and [`finish`](Self::finish) returns the string
+/// built. If the writer is dropped without finishing, written bytes are
+/// discarded when possible.
+pub struct ArenaWriter<'a, A: BackingAlloc = Global> {
+    region: Region<'a, A>,
+    err: Option<Error>,
+}
+
+impl<'a, A: BackingAlloc> ArenaWriter<'a, A> {
+    #[inline]
+    pub(crate) fn new(arena: &'a StringArena<A>) -> Self {
+        ArenaWriter {
+            region: Region::new(arena),
+            err: None,
+        }
+    }
+
+    /// Returns bytes written by this writer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.region.len()
+    }
+
+    /// Returns whether nothing is written by this writer.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Finish writing and returns the string ref.
+    /// Returns the error which caused previous write to fail.
+    #[inline]
+    pub fn finish(self) -> Result<&'a str> {
+        if let Some(err) = self.err {
+            return Err(err);
+        }
+        let bs = self.region.commit();
+        // SAFETY:
+        //
+        // Only strings are written by this writer.
+        Ok(unsafe { core::str::from_utf8_unchecked(bs) })
+    }
+
+    /// Mark the writer as failed, if no error occurs before.
+    #[inline]
+    pub(crate) fn fail(&mut self, err: Error) {
+        self.err.get_or_insert(err);
+    }
+}
+
+impl<A: BackingAlloc> fmt::Write for ArenaWriter<'_, A> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.err.is_some() {
+            return Err(fmt::Error);
+        }
+        self.region.write(s.as_bytes()).map_err(|e| {
+            self.err = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// ArenaByteWriter writes bytes directly into [`StringArena`] through
+/// [`std::io::Write`].
+///
+/// It is returned by [`StringArena::byte_writer`]. All bytes written are
+/// stored contiguously, and [`finish`](Self::finish) returns them. If the
+/// writer is dropped without finishing, written bytes are discarded when
+/// possible.
+#[cfg(feature = "std")]
+pub struct ArenaByteWriter<'a, A: BackingAlloc = Global> {
+    region: Region<'a, A>,
+}
+
+#[cfg(feature = "std")]
+impl<'a, A: BackingAlloc> ArenaByteWriter<'a, A> {
+    #[inline]
+    pub(crate) fn new(arena: &'a StringArena<A>) -> Self {
+        ArenaByteWriter {
+            region: Region::new(arena),
+        }
+    }
+
+    /// Returns bytes written by this writer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.region.len()
+    }
+
+    /// Returns whether nothing is written by this writer.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Finish writing and returns the byte slice ref.
+    #[inline]
+    pub fn finish(self) -> &'a [u8] {
+        self.region.commit()
+    }
+
+    /// Finish writing and returns the string ref if written bytes are
+    /// valid UTF-8, otherwise written bytes are discarded.
+    #[inline]
+    pub fn finish_str(self) -> Result<&'a str> {
+        // SAFETY:
+        //
+        // The region is written by this writer.
+        let bs = unsafe { self.region.arena.bytes_unchecked(self.region.start, self.region.end) };
+        core::str::from_utf8(bs).map_err(|e| Error::InvalidUtf8(e.valid_up_to()))?;
+        let bs = self.region.commit();
+        // SAFETY:
+        //
+        // The bytes are validated above.
+        Ok(unsafe { core::str::from_utf8_unchecked(bs) })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A: BackingAlloc> std::io::Write for ArenaByteWriter<'_, A> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.region
+            .write(buf)
+            .map(|_| buf.len())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::OutOfMemory, e))
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StringArena;
+    use std::fmt::Write;
+
+    #[test]
+    fn test_arena_writer() {
+        let sa = StringArena::with_capacity(32);
+        let mut w = sa.writer();
+        let name = "hello";
+        write!(w, "{}-{}", name, 42).unwrap();
+        assert_eq!(w.len(), 8);
+        let s1 = w.finish().unwrap();
+        assert_eq!(s1, "hello-42");
+        // dropped writer discards its bytes
+        let mut w = sa.writer();
+        w.write_str("discarded").unwrap();
+        drop(w);
+        assert_eq!(sa.len(), 8);
+        // interleaved add keeps result contiguous
+        let mut w = sa.writer();
+        w.write_str("ab").unwrap();
+        let s2 = sa.add("xy").unwrap();
+        w.write_str("cd").unwrap();
+        assert_eq!(w.finish().unwrap(), "abcd");
+        assert_eq!(s2, "xy");
+        // failed write returns error on finish
+        let mut w = sa.writer();
+        assert!(w.write_str("too long to fit in the arena").is_err());
+        assert!(w.finish().is_err());
+        assert_eq!(s1, "hello-42");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_arena_byte_writer() {
+        use std::io::Write;
+
+        let sa = StringArena::with_capacity(16);
+        let mut w = sa.byte_writer();
+        w.write_all(b"\xff\x00").unwrap();
+        assert_eq!(w.finish(), b"\xff\x00");
+        let mut w = sa.byte_writer();
+        write!(w, "{}", 42).unwrap();
+        assert_eq!(w.finish_str().unwrap(), "42");
+        let mut w = sa.byte_writer();
+        w.write_all(b"\xff").unwrap();
+        assert!(w.finish_str().is_err());
+        assert_eq!(sa.len(), 4);
+        let mut w = sa.byte_writer();
+        let err = w.write_all(&[0; 16]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::OutOfMemory);
+    }
+}